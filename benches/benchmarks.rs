@@ -80,7 +80,8 @@ fn setup_compiler(
     }
 
     if do_resolve {
-        let mut resolver = Resolver::new(&compiler);
+        let name_bindings = compiler.take_name_bindings();
+        let mut resolver = Resolver::new(&compiler, name_bindings);
         resolver.resolve();
 
         if !compiler.errors.is_empty() {
@@ -91,7 +92,8 @@ fn setup_compiler(
     }
 
     if do_typecheck {
-        let mut typechecker = Typechecker::new(&compiler);
+        let types = compiler.take_types();
+        let mut typechecker = Typechecker::new(&compiler, types);
         typechecker.typecheck();
 
         if !compiler.errors.is_empty() {
@@ -117,7 +119,8 @@ pub fn parse(mut compiler: Compiler, tokens: Tokens) {
 
 /// Resolve only
 pub fn resolve(mut compiler: Compiler, do_merge: bool) {
-    let mut resolver = Resolver::new(&compiler);
+    let name_bindings = compiler.take_name_bindings();
+    let mut resolver = Resolver::new(&compiler, name_bindings);
     resolver.resolve();
 
     if !compiler.errors.is_empty() {
@@ -132,7 +135,8 @@ pub fn resolve(mut compiler: Compiler, do_merge: bool) {
 
 /// Typecheck only
 pub fn typecheck(mut compiler: Compiler, do_merge: bool) {
-    let mut typechecker = Typechecker::new(&compiler);
+    let types = compiler.take_types();
+    let mut typechecker = Typechecker::new(&compiler, types);
     typechecker.typecheck();
 
     if !compiler.errors.is_empty() {
@@ -162,7 +166,8 @@ pub fn compile(mut compiler: Compiler, span_offset: usize) {
         exit(1);
     }
 
-    let mut resolver = Resolver::new(&compiler);
+    let name_bindings = compiler.take_name_bindings();
+    let mut resolver = Resolver::new(&compiler, name_bindings);
     resolver.resolve();
 
     if !compiler.errors.is_empty() {
@@ -172,7 +177,8 @@ pub fn compile(mut compiler: Compiler, span_offset: usize) {
 
     compiler.merge_name_bindings(resolver.to_name_bindings());
 
-    let mut typechecker = Typechecker::new(&compiler);
+    let types = compiler.take_types();
+    let mut typechecker = Typechecker::new(&compiler, types);
     typechecker.typecheck();
 
     if !compiler.errors.is_empty() {
@@ -1,4 +1,4 @@
-use crate::errors::SourceError;
+use crate::errors::{Severity, SourceError};
 use crate::parser::{
     AstNode, Block, Call, InOutTypes, List, Match, NodeId, Params, Pipeline, Record, Table,
     TypeArgs,
@@ -7,26 +7,94 @@ use crate::protocol::Command;
 use crate::resolver::{
     DeclId, Frame, NameBindings, ScopeId, TypeDecl, TypeDeclId, VarId, Variable,
 };
-use crate::typechecker::{TypeId, Types};
-use std::collections::HashMap;
+use crate::typechecker::{ClosureType, InOutType, Type, TypeId, TypeVar, Typechecker, Types};
+use crate::visitor::{children, walk, Visitor};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 
+/// State of the parser's tables, used to undo speculative parsing
+#[derive(Clone)]
 pub struct RollbackPoint {
-    idx_span_start: usize,
-    idx_nodes: usize,
-    idx_errors: usize,
-    idx_blocks: usize,
-    idx_params: usize,
-    idx_in_out_types: usize,
-    idx_calls: usize,
-    idx_lists: usize,
-    idx_tables: usize,
-    idx_records: usize,
-    idx_matches: usize,
-    idx_type_args: usize,
+    num_nodes: usize,
+    /// Lengths of the side tables, in the order of `Compiler::parser_tables()`
+    side_table_lens: Vec<usize>,
     token_pos: usize,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// State of the compiler's tables before a file was added, used to remove or replace the file
+#[derive(Clone)]
+struct FileStart {
+    /// State of the parser's tables before the current contents of the file were added
+    rollback_point: RollbackPoint,
+    /// Errors found while parsing the current contents of the file, as a range of `Compiler::errors`
+    parse_errors: Range<usize>,
+    /// Lengths of the tables of the later stages, which are truncated to them when the file is
+    /// removed or replaced. Only the errors before `num_errors` are kept as they are.
+    table_lens: TableLens,
+}
+
+/// Lengths of the tables filled by the resolver and the typechecker, and of the errors
+#[derive(Clone)]
+struct TableLens {
+    num_errors: usize,
+    num_node_types: usize,
+    num_types: usize,
+    num_record_types: usize,
+    num_closure_types: usize,
+    num_oneof_types: usize,
+    num_allof_types: usize,
+    num_type_vars: usize,
+    num_scopes: usize,
+    num_scope_stack: usize,
+    num_file_scopes: usize,
+    num_variables: usize,
+    num_type_decls: usize,
+    num_decls: usize,
+}
+
+/// Table indexed or keyed by NodeId
+trait NodeTable {
+    /// Remove the entries of the nodes at or after `num_nodes`
+    fn truncate_nodes(&mut self, num_nodes: usize);
+}
+
+impl<T> NodeTable for Vec<T> {
+    fn truncate_nodes(&mut self, num_nodes: usize) {
+        self.truncate(num_nodes);
+    }
+}
+
+impl<T> NodeTable for HashMap<NodeId, T> {
+    fn truncate_nodes(&mut self, num_nodes: usize) {
+        self.retain(|node_id, _| node_id.0 < num_nodes);
+    }
+}
+
+/// Table indexed by its own ID, e.g., blocks indexed by BlockId
+trait SideTable {
+    fn len(&self) -> usize;
+
+    fn truncate(&mut self, len: usize);
+}
+
+impl<T> SideTable for Vec<T> {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.truncate(len);
+    }
+}
+
+/// Version of the JSON produced by the `to_json()` methods of the compiler stages. It is
+/// increased whenever the output changes in a way that can break its consumers.
+pub const JSON_FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
 pub struct Span {
     pub start: usize,
     pub end: usize,
@@ -38,6 +106,43 @@ impl Span {
     }
 }
 
+impl From<Span> for nu_protocol::Span {
+    fn from(span: Span) -> Self {
+        nu_protocol::Span::new(span.start, span.end)
+    }
+}
+
+impl From<nu_protocol::Span> for Span {
+    fn from(span: nu_protocol::Span) -> Self {
+        Span::new(span.start, span.end)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct FileId(pub usize);
+
+/// A source file added to the compiler
+#[derive(Debug, Clone)]
+pub struct File {
+    pub name: String,
+    /// Start of the file contents in `Compiler::source`
+    pub start: usize,
+    /// End of the file contents in `Compiler::source`
+    pub end: usize,
+    /// Root block of the file, set once the file is parsed
+    pub root: Option<NodeId>,
+}
+
+/// Position in a source file, for diagnostics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub file: FileId,
+    /// Line number, starting at 1
+    pub line: usize,
+    /// Column in characters, starting at 1
+    pub column: usize,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Spanned<T> {
     pub item: T,
@@ -68,13 +173,24 @@ pub struct Compiler {
     pub type_args: Vec<TypeArgs>,      // TypeArgs, indexed by TypeArgsId
     pub pipelines: Vec<Pipeline>,      // Pipelines, indexed by PipelineId
     pub source: Vec<u8>,
-    pub file_offsets: Vec<(String, usize, usize)>, // fname, start, end
+    /// Source files, indexed by FileId
+    pub files: Vec<File>,
+    /// State of the tables before each file was added, indexed by FileId
+    file_starts: Vec<FileStart>,
+    /// Spans of comments documenting `Def`, `Extern` and `Param` nodes
+    pub doc_comments: HashMap<NodeId, Span>,
+    /// `Attribute` nodes preceding `Def` and `Extern` nodes, in source order
+    pub attributes: HashMap<NodeId, Vec<NodeId>>,
+    /// Parent of each node, indexed by NodeId. Built on first use and reset when nodes are added.
+    parents: OnceCell<Vec<Option<NodeId>>>,
 
     // name bindings:
     /// All scope frames ever entered, indexed by ScopeId
     pub scope: Vec<Frame>,
     /// Stack of currently entered scope frames
     pub scope_stack: Vec<ScopeId>,
+    /// Scope frame of the root block of each resolved file, indexed by FileId
+    pub file_scopes: Vec<ScopeId>,
     /// Variables, indexed by VarId
     pub variables: Vec<Variable>,
     /// Mapping of variable's name node -> Variable
@@ -90,6 +206,18 @@ pub struct Compiler {
     /// Mapping of decl's name node -> Command
     pub decl_resolution: HashMap<NodeId, DeclId>,
 
+    // types (besides node_types), see the typechecker:
+    pub types: Vec<Type>,
+    pub record_types: Vec<Vec<(NodeId, TypeId)>>,
+    pub closure_types: Vec<ClosureType>,
+    pub oneof_types: Vec<HashSet<TypeId>>,
+    pub allof_types: Vec<HashSet<TypeId>>,
+    pub type_vars: Vec<TypeVar>,
+    pub variable_types: Vec<TypeId>,
+    pub decl_types: Vec<Vec<InOutType>>,
+    pub type_param_bounds: HashMap<TypeDeclId, TypeId>,
+    pub recursive_aliases: HashSet<TypeDeclId>,
+
     // Definitions:
     // indexed by FunId
     // pub functions: Vec<Function>,
@@ -124,10 +252,15 @@ impl Compiler {
             type_args: vec![],
             pipelines: vec![],
             source: vec![],
-            file_offsets: vec![],
+            files: vec![],
+            file_starts: vec![],
+            doc_comments: HashMap::new(),
+            attributes: HashMap::new(),
+            parents: OnceCell::new(),
 
             scope: vec![],
             scope_stack: vec![],
+            file_scopes: vec![],
             variables: vec![],
             var_resolution: HashMap::new(),
             type_decls: vec![],
@@ -136,6 +269,17 @@ impl Compiler {
             decl_nodes: vec![],
             decl_resolution: HashMap::new(),
 
+            types: vec![],
+            record_types: vec![],
+            closure_types: vec![],
+            oneof_types: vec![],
+            allof_types: vec![],
+            type_vars: vec![],
+            variable_types: vec![],
+            decl_types: vec![],
+            type_param_bounds: HashMap::new(),
+            recursive_aliases: HashSet::new(),
+
             // variables: vec![],
             // functions: vec![],
             // types: vec![],
@@ -163,7 +307,12 @@ impl Compiler {
 
             if matches!(
                 ast_node,
-                AstNode::Name | AstNode::Variable | AstNode::Int | AstNode::Float | AstNode::String
+                AstNode::Name
+                    | AstNode::Variable
+                    | AstNode::Int
+                    | AstNode::Float
+                    | AstNode::String
+                    | AstNode::PatternBinding
             ) {
                 result.push_str(&format!(
                     " \"{}\"",
@@ -174,44 +323,455 @@ impl Compiler {
             result.push('\n');
         }
 
+        if !self.doc_comments.is_empty() {
+            result.push_str("==== DOC COMMENTS ====\n");
+            let mut node_ids: Vec<_> = self.doc_comments.keys().collect();
+            node_ids.sort_by_key(|node_id| node_id.0);
+            for node_id in node_ids {
+                let doc = self.get_doc_comment(*node_id).unwrap_or_default();
+                result.push_str(&format!("{}: {:?}\n", node_id.0, doc));
+            }
+        }
+
+        if !self.attributes.is_empty() {
+            result.push_str("==== ATTRIBUTES ====\n");
+            let mut node_ids: Vec<_> = self.attributes.keys().collect();
+            node_ids.sort_by_key(|node_id| node_id.0);
+            for node_id in node_ids {
+                let attributes: Vec<_> = self.attributes[node_id].iter().map(|id| id.0).collect();
+                result.push_str(&format!("{}: {:?}\n", node_id.0, attributes));
+            }
+        }
+
         if !self.errors.is_empty() {
             result.push_str("==== COMPILER ERRORS ====\n");
             for error in &self.errors {
-                result.push_str(&format!(
-                    "{:?} (NodeId {}): {}\n",
-                    error.severity, error.node_id.0, error.message
-                ));
+                result.push_str(&format!("{}\n", error.display(self)));
             }
         }
 
         result
     }
 
+    /// Displays the AST as an indented tree starting from `root`, one node per line with its source
+    /// and, if a typechecker is given, its type. Unlike `display_state`, the output does not
+    /// depend on NodeIds, which makes diffs of snapshots easier to review.
+    pub fn display_tree(&self, root: NodeId, typechecker: Option<&Typechecker>) -> String {
+        let mut renderer = TreeRenderer {
+            typechecker,
+            depth: 0,
+            result: "==== TREE ====\n".to_string(),
+        };
+        walk(self, root, &mut renderer);
+        renderer.result
+    }
+
+    /// Serialize the files, the AST nodes with their spans, payloads and children, and the errors
+    pub fn to_json(&self) -> Value {
+        self.nodes_to_json(&self.files, 0..self.ast_nodes.len(), &self.errors)
+    }
+
+    /// Serialize a file like `to_json()`, but with only the nodes parsed from it and their errors
+    pub fn file_to_json(&self, file_id: FileId) -> Value {
+        let errors: Vec<_> = self
+            .errors
+            .iter()
+            .filter(|error| self.file_of_node(error.node_id) == Some(file_id))
+            .cloned()
+            .collect();
+
+        self.nodes_to_json(
+            std::slice::from_ref(&self.files[file_id.0]),
+            self.file_nodes(file_id),
+            &errors,
+        )
+    }
+
+    fn nodes_to_json(&self, files: &[File], nodes: Range<usize>, errors: &[SourceError]) -> Value {
+        let files: Vec<_> = files
+            .iter()
+            .map(|file| {
+                json!({
+                    "name": file.name,
+                    "start": file.start,
+                    "end": file.end,
+                    "root": file.root.map(|root| root.0),
+                })
+            })
+            .collect();
+
+        let doc_comments: Vec<_> = nodes
+            .clone()
+            .map(NodeId)
+            .filter(|node_id| self.doc_comments.contains_key(node_id))
+            .map(|node_id| json!({ "node_id": node_id.0, "doc": self.get_doc_comment(node_id) }))
+            .collect();
+
+        let nodes: Vec<_> = nodes
+            .map(|idx| {
+                json!({
+                    "id": idx,
+                    "node": self.ast_nodes[idx],
+                    "span": self.spans[idx],
+                    "children": children(self, NodeId(idx))
+                        .into_iter()
+                        .map(|child| child.0)
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+
+        json!({
+            "files": files,
+            "nodes": nodes,
+            "doc_comments": doc_comments,
+            "errors": errors.iter().map(|error| error.to_json(self)).collect::<Vec<_>>(),
+        })
+    }
+
+    /// Whether any errors (as opposed to warnings or notes) have been reported
+    pub fn has_errors(&self) -> bool {
+        self.errors
+            .iter()
+            .any(|error| error.severity == Severity::Error)
+    }
+
+    /// Move the name bindings out of the compiler, for a resolver to continue from them without
+    /// copying them. They are stored back with `merge_name_bindings()`.
+    pub fn take_name_bindings(&mut self) -> NameBindings {
+        NameBindings {
+            scope: std::mem::take(&mut self.scope),
+            scope_stack: std::mem::take(&mut self.scope_stack),
+            file_scopes: std::mem::take(&mut self.file_scopes),
+            variables: std::mem::take(&mut self.variables),
+            var_resolution: std::mem::take(&mut self.var_resolution),
+            type_decls: std::mem::take(&mut self.type_decls),
+            type_resolution: std::mem::take(&mut self.type_resolution),
+            decls: std::mem::take(&mut self.decls),
+            decl_nodes: std::mem::take(&mut self.decl_nodes),
+            decl_resolution: std::mem::take(&mut self.decl_resolution),
+            errors: vec![],
+        }
+    }
+
+    /// Store the name bindings of a resolver, which started from the name bindings taken from the
+    /// compiler, replacing them. Only the errors found by the resolver are added.
     pub fn merge_name_bindings(&mut self, name_bindings: NameBindings) {
-        self.scope.extend(name_bindings.scope);
-        self.scope_stack.extend(name_bindings.scope_stack);
-        self.variables.extend(name_bindings.variables);
-        self.var_resolution.extend(name_bindings.var_resolution);
-        self.type_decls.extend(name_bindings.type_decls);
-        self.type_resolution.extend(name_bindings.type_resolution);
-        self.decls.extend(name_bindings.decls);
-        self.decl_nodes.extend(name_bindings.decl_nodes);
-        self.decl_resolution.extend(name_bindings.decl_resolution);
+        self.scope = name_bindings.scope;
+        self.scope_stack = name_bindings.scope_stack;
+        self.file_scopes = name_bindings.file_scopes;
+        self.variables = name_bindings.variables;
+        self.var_resolution = name_bindings.var_resolution;
+        self.type_decls = name_bindings.type_decls;
+        self.type_resolution = name_bindings.type_resolution;
+        self.decls = name_bindings.decls;
+        self.decl_nodes = name_bindings.decl_nodes;
+        self.decl_resolution = name_bindings.decl_resolution;
         self.errors.extend(name_bindings.errors);
     }
 
+    /// Move the types out of the compiler, for a typechecker to continue from them without copying
+    /// them. They are stored back with `merge_types()`.
+    pub fn take_types(&mut self) -> Types {
+        Types {
+            types: std::mem::take(&mut self.types),
+            node_types: std::mem::take(&mut self.node_types),
+            record_types: std::mem::take(&mut self.record_types),
+            closure_types: std::mem::take(&mut self.closure_types),
+            oneof_types: std::mem::take(&mut self.oneof_types),
+            allof_types: std::mem::take(&mut self.allof_types),
+            type_vars: std::mem::take(&mut self.type_vars),
+            variable_types: std::mem::take(&mut self.variable_types),
+            decl_types: std::mem::take(&mut self.decl_types),
+            type_param_bounds: std::mem::take(&mut self.type_param_bounds),
+            recursive_aliases: std::mem::take(&mut self.recursive_aliases),
+            errors: vec![],
+        }
+    }
+
+    /// Store the types of a typechecker, which started from the types taken from the compiler,
+    /// replacing them. Only the errors found by the typechecker are added.
     pub fn merge_types(&mut self, types: Types) {
-        self.node_types.extend(types.node_types);
+        self.types = types.types;
+        self.node_types = types.node_types;
+        self.record_types = types.record_types;
+        self.closure_types = types.closure_types;
+        self.oneof_types = types.oneof_types;
+        self.allof_types = types.allof_types;
+        self.type_vars = types.type_vars;
+        self.variable_types = types.variable_types;
+        self.decl_types = types.decl_types;
+        self.type_param_bounds = types.type_param_bounds;
+        self.recursive_aliases = types.recursive_aliases;
         self.errors.extend(types.errors);
     }
 
-    pub fn add_file(&mut self, fname: &str, contents: &[u8]) {
+    pub fn add_file(&mut self, fname: &str, contents: &[u8]) -> FileId {
         let span_offset = self.source.len();
 
-        self.file_offsets
-            .push((fname.to_string(), span_offset, span_offset + contents.len()));
+        let file_start = FileStart {
+            rollback_point: self.get_rollback_point(0),
+            parse_errors: self.errors.len()..self.errors.len(),
+            table_lens: self.table_lens(),
+        };
+        self.file_starts.push(file_start);
+
+        self.files.push(File {
+            name: fname.to_string(),
+            start: span_offset,
+            end: span_offset + contents.len(),
+            root: None,
+        });
 
         self.source.extend_from_slice(contents);
+
+        FileId(self.files.len() - 1)
+    }
+
+    fn table_lens(&self) -> TableLens {
+        TableLens {
+            num_errors: self.errors.len(),
+            num_node_types: self.node_types.len(),
+            num_types: self.types.len(),
+            num_record_types: self.record_types.len(),
+            num_closure_types: self.closure_types.len(),
+            num_oneof_types: self.oneof_types.len(),
+            num_allof_types: self.allof_types.len(),
+            num_type_vars: self.type_vars.len(),
+            num_scopes: self.scope.len(),
+            num_scope_stack: self.scope_stack.len(),
+            num_file_scopes: self.file_scopes.len(),
+            num_variables: self.variables.len(),
+            num_type_decls: self.type_decls.len(),
+            num_decls: self.decls.len(),
+        }
+    }
+
+    /// Set the root block of the file whose contents were added last, once they are parsed
+    pub fn set_file_root(&mut self, root: NodeId) {
+        let Some(idx) = (0..self.files.len()).max_by_key(|idx| self.files[*idx].start) else {
+            return;
+        };
+
+        self.files[idx].root = Some(root);
+        self.file_starts[idx].parse_errors.end = self.errors.len();
+    }
+
+    /// Remove a file with its name bindings, types and errors
+    ///
+    /// The files added after it are kept with their nodes, but they are resolved and typechecked
+    /// again by the next runs of the resolver and the typechecker, since they may have used the
+    /// declarations of the removed file. Returns whether a file had the given name.
+    pub fn remove_file(&mut self, fname: &str) -> bool {
+        let Some(idx) = self.files.iter().position(|file| file.name == fname) else {
+            return false;
+        };
+
+        self.reset_file(idx);
+        self.files.remove(idx);
+        self.file_starts.remove(idx);
+
+        true
+    }
+
+    /// Replace the contents of a file, which must be lexed and parsed next, like a new file. The
+    /// new contents start at the `start` of the returned file, which is the span offset to lex them
+    /// with.
+    ///
+    /// Only the replaced file is parsed again, while the nodes of the other files are kept. Then the
+    /// next runs of the resolver and the typechecker update the replaced file and the files added
+    /// after it, which may use its declarations. An unknown file is added as a new file.
+    ///
+    /// The new contents are added after all sources, with NodeIds after all nodes. The old nodes of
+    /// a file are removed only if no file was parsed after it, and are otherwise left unused.
+    pub fn replace_file(&mut self, fname: &str, contents: &[u8]) -> FileId {
+        let Some(idx) = self.files.iter().position(|file| file.name == fname) else {
+            return self.add_file(fname, contents);
+        };
+
+        self.reset_file(idx);
+
+        self.file_starts[idx].rollback_point = self.get_rollback_point(0);
+        self.file_starts[idx].parse_errors = self.errors.len()..self.errors.len();
+
+        let span_offset = self.source.len();
+        self.files[idx] = File {
+            name: fname.to_string(),
+            start: span_offset,
+            end: span_offset + contents.len(),
+            root: None,
+        };
+        self.source.extend_from_slice(contents);
+
+        FileId(idx)
+    }
+
+    /// Remove the nodes of a file, if they are the last ones, and the name bindings, types and
+    /// errors of the file and of the files added after it
+    fn reset_file(&mut self, idx: usize) {
+        let file_start = self.file_starts[idx].clone();
+        let lens = file_start.table_lens;
+
+        // nodes of the file and of the later ones are at or after the first node of one of them,
+        // since replaced files are parsed again after all nodes
+        let num_nodes = self.file_starts[idx..]
+            .iter()
+            .map(|file_start| file_start.rollback_point.num_nodes)
+            .min()
+            .unwrap_or_default();
+
+        // errors found while parsing the other files are kept, since they are not parsed again
+        let mut errors = self.errors[..lens.num_errors].to_vec();
+        for (other_idx, other_start) in self.file_starts.iter_mut().enumerate() {
+            let parse_errors = other_start.parse_errors.clone();
+            if other_idx != idx && parse_errors.start >= lens.num_errors {
+                other_start.parse_errors = errors.len()..errors.len() + parse_errors.len();
+                errors.extend_from_slice(&self.errors[parse_errors]);
+            }
+        }
+
+        let file = &self.files[idx];
+        let is_last_parsed =
+            self.files
+                .iter()
+                .zip(&self.file_starts)
+                .all(|(other, other_start)| {
+                    other.start <= file.start
+                        && other_start.rollback_point.num_nodes
+                            <= file_start.rollback_point.num_nodes
+                });
+        if is_last_parsed {
+            self.source.truncate(self.files[idx].start);
+            self.apply_compiler_rollback(file_start.rollback_point);
+        }
+        self.errors = errors;
+
+        // the later files are resolved and typechecked again from the same state as this one
+        for later_start in &mut self.file_starts[idx..] {
+            later_start.table_lens = lens.clone();
+        }
+        self.file_scopes.truncate(lens.num_file_scopes);
+
+        self.node_types.truncate(lens.num_node_types);
+
+        self.scope.truncate(lens.num_scopes);
+        self.scope_stack.truncate(lens.num_scope_stack);
+        self.variables.truncate(lens.num_variables);
+        self.var_resolution.truncate_nodes(num_nodes);
+        self.type_decls.truncate(lens.num_type_decls);
+        self.type_resolution.truncate_nodes(num_nodes);
+        self.decls.truncate(lens.num_decls);
+        self.decl_nodes.truncate(lens.num_decls);
+        self.decl_resolution.truncate_nodes(num_nodes);
+
+        self.types.truncate(lens.num_types);
+        self.record_types.truncate(lens.num_record_types);
+        self.closure_types.truncate(lens.num_closure_types);
+        self.oneof_types.truncate(lens.num_oneof_types);
+        self.allof_types.truncate(lens.num_allof_types);
+        self.type_vars.truncate(lens.num_type_vars);
+        self.variable_types.truncate(lens.num_variables);
+        self.decl_types.truncate(lens.num_decls);
+        self.type_param_bounds
+            .retain(|type_decl_id, _| type_decl_id.0 < lens.num_type_decls);
+        self.recursive_aliases
+            .retain(|type_decl_id| type_decl_id.0 < lens.num_type_decls);
+    }
+
+    /// Get the file containing a byte offset, which is relative to the start of all sources, like
+    /// spans are. The end of a file counts as part of it only if no other file starts there, so
+    /// the location of a node is better found with `file_of_node()`.
+    pub fn file_of_offset(&self, offset: usize) -> Option<FileId> {
+        // replaced files are added after all sources, so the files are not sorted by their start
+        let idx = self
+            .files
+            .iter()
+            .position(|file| file.start <= offset && offset < file.end)
+            .or_else(|| self.files.iter().position(|file| file.end == offset))?;
+
+        Some(FileId(idx))
+    }
+
+    /// Get the file a span comes from
+    pub fn file_of(&self, span: Span) -> Option<FileId> {
+        self.file_of_offset(span.start)
+    }
+
+    /// Get the file whose parsing created a node, even if the node is at the very end of the file
+    ///
+    /// Nodes left from the old contents of a replaced file do not belong to any file.
+    pub fn file_of_node(&self, node_id: NodeId) -> Option<FileId> {
+        let idx = (0..self.files.len())
+            .position(|idx| self.file_nodes(FileId(idx)).contains(&node_id.0))?;
+
+        Some(FileId(idx))
+    }
+
+    /// Get the range of NodeIds of the nodes parsed from a file
+    fn file_nodes(&self, file_id: FileId) -> Range<usize> {
+        // the root block is the last node of a parsed file
+        let start = self.file_starts[file_id.0].rollback_point.num_nodes;
+        let end = self.files[file_id.0]
+            .root
+            .map_or(self.ast_nodes.len(), |root| root.0 + 1);
+
+        start..end
+    }
+
+    /// Get the line and column of a byte offset in its file
+    pub fn location(&self, offset: usize) -> Option<Location> {
+        let file_id = self.file_of_offset(offset)?;
+        Some(self.location_in_file(file_id, offset))
+    }
+
+    /// Get the line and column of the start of a node in its file
+    pub fn node_location(&self, node_id: NodeId) -> Option<Location> {
+        let file_id = self.file_of_node(node_id)?;
+        Some(self.location_in_file(file_id, self.get_span(node_id).start))
+    }
+
+    fn location_in_file(&self, file_id: FileId, offset: usize) -> Location {
+        let file = &self.files[file_id.0];
+        // spans of nodes in broken files may reach past the end of the file
+        let before = &self.source[file.start..offset.clamp(file.start, file.end)];
+        let line_start = before
+            .iter()
+            .rposition(|c| *c == b'\n')
+            .map_or(0, |pos| pos + 1);
+
+        Location {
+            file: file_id,
+            line: before.iter().filter(|c| **c == b'\n').count() + 1,
+            column: String::from_utf8_lossy(&before[line_start..])
+                .chars()
+                .count()
+                + 1,
+        }
+    }
+
+    /// Format the location of a byte offset as `file:line:column`, or just the offset if it is not
+    /// in any file
+    pub fn display_location(&self, offset: usize) -> String {
+        match self.location(offset) {
+            Some(location) => self.display_file_location(location),
+            None => offset.to_string(),
+        }
+    }
+
+    /// Format the location of the start of a node as `file:line:column`, or just its offset if it
+    /// is not in any file
+    pub fn display_node_location(&self, node_id: NodeId) -> String {
+        match self.node_location(node_id) {
+            Some(location) => self.display_file_location(location),
+            None => self.get_span(node_id).start.to_string(),
+        }
+    }
+
+    fn display_file_location(&self, location: Location) -> String {
+        format!(
+            "{}:{}:{}",
+            self.files[location.file.0].name, location.line, location.column
+        )
     }
 
     pub fn span_offset(&self) -> usize {
@@ -228,45 +788,195 @@ impl Compiler {
 
     pub fn push_node(&mut self, ast_node: AstNode) -> NodeId {
         self.ast_nodes.push(ast_node);
+        self.parents.take();
 
         NodeId(self.ast_nodes.len() - 1)
     }
 
-    pub fn get_rollback_point(&self, token_pos: usize) -> RollbackPoint {
+    /// Tables filled by the parser, which are restored by a rollback
+    ///
+    /// The compiler is destructured without `..`, so a new field must be listed here (or
+    /// explicitly ignored) before the code compiles.
+    fn parser_tables(&mut self) -> ([&mut dyn NodeTable; 4], [&mut dyn SideTable; 11]) {
+        let Compiler {
+            spans,
+            ast_nodes,
+            doc_comments,
+            attributes,
+            blocks,
+            params,
+            in_out_types,
+            calls,
+            lists,
+            tables,
+            records,
+            matches,
+            type_args,
+            pipelines,
+            errors,
+            // reset by `push_node()` and `apply_compiler_rollback()`
+            parents: _,
+            // only the root of the file is set, once parsing is done
+            files: _,
+            // not modified by the parser
+            node_types: _,
+            source: _,
+            file_starts: _,
+            scope: _,
+            scope_stack: _,
+            file_scopes: _,
+            variables: _,
+            var_resolution: _,
+            type_decls: _,
+            type_resolution: _,
+            decls: _,
+            decl_nodes: _,
+            decl_resolution: _,
+            types: _,
+            record_types: _,
+            closure_types: _,
+            oneof_types: _,
+            allof_types: _,
+            type_vars: _,
+            variable_types: _,
+            decl_types: _,
+            type_param_bounds: _,
+            recursive_aliases: _,
+        } = self;
+
+        (
+            [spans, ast_nodes, doc_comments, attributes],
+            [
+                blocks,
+                params,
+                in_out_types,
+                calls,
+                lists,
+                tables,
+                records,
+                matches,
+                type_args,
+                pipelines,
+                errors,
+            ],
+        )
+    }
+
+    pub fn get_rollback_point(&mut self, token_pos: usize) -> RollbackPoint {
+        debug_assert_eq!(self.spans.len(), self.ast_nodes.len());
+        let num_nodes = self.ast_nodes.len();
+        let (_, side_tables) = self.parser_tables();
+
         RollbackPoint {
-            idx_span_start: self.spans.len(),
-            idx_nodes: self.ast_nodes.len(),
-            idx_errors: self.errors.len(),
-            idx_blocks: self.blocks.len(),
-            idx_params: self.params.len(),
-            idx_in_out_types: self.in_out_types.len(),
-            idx_calls: self.calls.len(),
-            idx_lists: self.lists.len(),
-            idx_tables: self.tables.len(),
-            idx_records: self.records.len(),
-            idx_matches: self.matches.len(),
-            idx_type_args: self.type_args.len(),
+            num_nodes,
+            side_table_lens: side_tables.iter().map(|table| table.len()).collect(),
             token_pos,
         }
     }
 
     pub fn apply_compiler_rollback(&mut self, rbp: RollbackPoint) -> usize {
-        self.blocks.truncate(rbp.idx_blocks);
-        self.params.truncate(rbp.idx_params);
-        self.in_out_types.truncate(rbp.idx_in_out_types);
-        self.calls.truncate(rbp.idx_calls);
-        self.lists.truncate(rbp.idx_lists);
-        self.tables.truncate(rbp.idx_tables);
-        self.records.truncate(rbp.idx_records);
-        self.matches.truncate(rbp.idx_matches);
-        self.type_args.truncate(rbp.idx_type_args);
-        self.ast_nodes.truncate(rbp.idx_nodes);
-        self.errors.truncate(rbp.idx_errors);
-        self.spans.truncate(rbp.idx_span_start);
+        let (node_tables, side_tables) = self.parser_tables();
+
+        for table in node_tables {
+            table.truncate_nodes(rbp.num_nodes);
+        }
+        for (table, len) in side_tables.into_iter().zip(rbp.side_table_lens) {
+            table.truncate(len);
+        }
+        self.parents.take();
 
         rbp.token_pos
     }
 
+    /// Get the parent of a node, or `None` for the root block of a file and for nodes left outside
+    /// of the tree by parse errors
+    pub fn get_parent(&self, node_id: NodeId) -> Option<NodeId> {
+        let parents = self.parents.get_or_init(|| {
+            let mut parents = vec![None; self.ast_nodes.len()];
+            for idx in 0..self.ast_nodes.len() {
+                for child in children(self, NodeId(idx)) {
+                    parents[child.0] = Some(NodeId(idx));
+                }
+            }
+            parents
+        });
+
+        parents[node_id.0]
+    }
+
+    /// Get the ancestors of a node, starting with its parent and ending with the root block
+    pub fn ancestors(&self, node_id: NodeId) -> Vec<NodeId> {
+        let mut ancestors = vec![];
+        let mut current = node_id;
+        while let Some(parent) = self.get_parent(current) {
+            ancestors.push(parent);
+            current = parent;
+        }
+        ancestors
+    }
+
+    /// Get the innermost `Def` containing a node
+    pub fn enclosing_def(&self, node_id: NodeId) -> Option<NodeId> {
+        self.ancestors(node_id)
+            .into_iter()
+            .find(|ancestor| matches!(self.ast_nodes[ancestor.0], AstNode::Def { .. }))
+    }
+
+    /// Get the innermost `Block` containing a node
+    pub fn enclosing_block(&self, node_id: NodeId) -> Option<NodeId> {
+        self.ancestors(node_id)
+            .into_iter()
+            .find(|ancestor| matches!(self.ast_nodes[ancestor.0], AstNode::Block(_)))
+    }
+
+    /// Get the innermost node whose span contains the byte offset, which is relative to the
+    /// start of all sources, like spans are
+    ///
+    /// The search starts from the root block of the file containing the offset and descends into
+    /// the child containing the offset, so nodes left outside of the tree are never returned.
+    pub fn node_at_offset(&self, offset: usize) -> Option<NodeId> {
+        let contains = |node_id: NodeId| {
+            let span = self.spans[node_id.0];
+            span.start <= offset && offset < span.end
+        };
+
+        let mut current = self
+            .files
+            .iter()
+            .filter_map(|file| file.root)
+            .find(|root| contains(*root))?;
+
+        while let Some(child) = children(self, current).into_iter().find(|c| contains(*c)) {
+            current = child;
+        }
+
+        Some(current)
+    }
+
+    /// Get the documentation of a `Def`, `Extern` or `Param` node, with the comment markers removed
+    pub fn get_doc_comment(&self, node_id: NodeId) -> Option<String> {
+        let span = self.doc_comments.get(&node_id)?;
+        let contents = String::from_utf8_lossy(self.get_span_contents_manual(span.start, span.end));
+
+        let lines: Vec<_> = contents
+            .lines()
+            .map(|line| {
+                let line = line.trim_start().trim_start_matches('#');
+                line.strip_prefix(' ').unwrap_or(line).trim_end()
+            })
+            .collect();
+
+        Some(lines.join("\n"))
+    }
+
+    /// Get the `Attribute` nodes attached to a `Def` or `Extern` node
+    pub fn get_attributes(&self, node_id: NodeId) -> &[NodeId] {
+        self.attributes
+            .get(&node_id)
+            .map(|attributes| attributes.as_slice())
+            .unwrap_or_default()
+    }
+
     /// Get span of node
     pub fn get_span(&self, node_id: NodeId) -> Span {
         *self
@@ -394,3 +1104,63 @@ impl Compiler {
         &self.type_args[type_args_id.0]
     }
 }
+
+/// Visitor rendering `Compiler::display_tree()`
+struct TreeRenderer<'a> {
+    typechecker: Option<&'a Typechecker<'a>>,
+    depth: usize,
+    result: String,
+}
+
+/// Longest source snippet shown in a tree line
+const MAX_SNIPPET_LEN: usize = 40;
+
+impl Visitor for TreeRenderer<'_> {
+    fn enter(&mut self, compiler: &Compiler, node_id: NodeId) -> bool {
+        let ast_node = compiler.get_node(node_id);
+
+        // the variant name, followed by the flags that are set, e.g., `Let is_mutable`
+        let mut line =
+            match serde_json::to_value(ast_node) {
+                Ok(Value::Object(variant)) => variant
+                    .iter()
+                    .flat_map(|(name, fields)| {
+                        let flags = fields.as_object().into_iter().flatten().filter_map(
+                            |(field, value)| (value == &Value::Bool(true)).then_some(field.clone()),
+                        );
+                        std::iter::once(name.clone()).chain(flags)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                Ok(Value::String(name)) => name,
+                _ => format!("{ast_node:?}"),
+            };
+
+        // spans of nodes in broken files may reach past the end of the source
+        let span = compiler.get_span(node_id);
+        let end = span.end.min(compiler.source.len());
+        let contents = compiler.source.get(span.start.min(end)..end);
+        let contents = String::from_utf8_lossy(contents.unwrap_or_default());
+        let first_line = contents.lines().next().unwrap_or_default();
+        let mut snippet: String = first_line.chars().take(MAX_SNIPPET_LEN).collect();
+        if snippet.len() < contents.len() {
+            snippet.push_str("...");
+        }
+        if !snippet.is_empty() {
+            line.push_str(&format!(" {snippet:?}"));
+        }
+
+        if let Some(typechecker) = self.typechecker {
+            line.push_str(&format!(": {}", typechecker.node_type_to_string(node_id)));
+        }
+
+        self.result
+            .push_str(&format!("{}{line}\n", "  ".repeat(self.depth)));
+        self.depth += 1;
+        true
+    }
+
+    fn exit(&mut self, _compiler: &Compiler, _node_id: NodeId) {
+        self.depth -= 1;
+    }
+}
@@ -1,8 +1,11 @@
+use crate::compiler::Compiler;
 use crate::parser::NodeId;
+use serde_json::{json, Value};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Severity {
     Error,
+    Warning,
     Note,
 }
 
@@ -12,3 +15,29 @@ pub struct SourceError {
     pub node_id: NodeId,
     pub severity: Severity,
 }
+
+impl SourceError {
+    /// Format the error as `file:line:column: severity: message`, located at the start of its node
+    pub fn display(&self, compiler: &Compiler) -> String {
+        format!(
+            "{}: {:?}: {}",
+            compiler.display_node_location(self.node_id),
+            self.severity,
+            self.message
+        )
+    }
+
+    /// Serialize the error for `Compiler::to_json()` and the other JSON outputs
+    pub fn to_json(&self, compiler: &Compiler) -> Value {
+        let location = compiler.node_location(self.node_id);
+
+        json!({
+            "severity": format!("{:?}", self.severity),
+            "node_id": self.node_id.0,
+            "message": self.message,
+            "file": location.map(|location| location.file.0),
+            "line": location.map(|location| location.line),
+            "column": location.map(|location| location.column),
+        })
+    }
+}
@@ -0,0 +1,995 @@
+use crate::compiler::{Compiler, Span};
+use crate::parser::{AstNode, NodeId};
+
+/// Maximum line width before a list, record, block or pipeline is split into multiple lines
+const MAX_WIDTH: usize = 100;
+
+/// Number of spaces per indentation level
+const INDENT_WIDTH: usize = 4;
+
+/// One line of a construct split into multiple lines
+#[derive(Debug, Clone, Copy)]
+enum Line {
+    /// A statement of a block or a parameter
+    Node(NodeId),
+    /// A list item or a table row
+    Item(NodeId),
+    /// The header of a table, followed by `;`
+    TableHeader(NodeId),
+    /// A record field, e.g., `key: value`
+    Field(NodeId, NodeId),
+    /// A match arm, e.g., `pattern => result`
+    Arm(NodeId, NodeId),
+}
+
+/// Which comments and blank lines are kept between lines
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Spacing {
+    /// Statements of a block keep comments and single blank lines
+    Statements,
+    /// Parameters keep comments, e.g., the ones documenting a parameter
+    Params,
+    /// Fields of records keep comments, which can only come before a key
+    Fields,
+    /// Items of lists, tables and matches, which cannot contain comments
+    Items,
+}
+
+/// Prints the AST of a file in canonical Nushell style, keeping its comments.
+///
+/// Lists, records, blocks and pipelines stay on one line if they fit into [MAX_WIDTH] and do not
+/// contain comments. Otherwise, they are split into one item per line. The result depends only on
+/// the AST, the comments and the blank lines between statements, so formatting is idempotent.
+pub struct Formatter<'a> {
+    // Immutable reference to a compiler after the parser pass
+    compiler: &'a Compiler,
+    /// Spans of all comments of the source, in source order, e.g., from `Tokens::comments()`
+    comments: Vec<Span>,
+    /// Index of the first comment not printed yet
+    next_comment: usize,
+    indent: usize,
+    /// Whether the indentation still needs to be written at the start of the current line
+    pending_indent: bool,
+    /// Whether we are trying to print a node on a single line
+    flat: bool,
+    /// Set if a node could not be printed on a single line while `flat` is set
+    flat_failed: bool,
+    output: String,
+}
+
+impl<'a> Formatter<'a> {
+    pub fn new(compiler: &'a Compiler, comments: Vec<Span>) -> Self {
+        Self {
+            compiler,
+            comments,
+            next_comment: 0,
+            indent: 0,
+            pending_indent: false,
+            flat: false,
+            flat_failed: false,
+            output: String::new(),
+        }
+    }
+
+    /// Formats the file with the given root block.
+    ///
+    /// The code must parse without errors, since broken code cannot be printed back faithfully.
+    pub fn format(mut self, root: NodeId) -> String {
+        let lines: Vec<_> = self
+            .compiler
+            .get_block(root)
+            .nodes
+            .iter()
+            .map(|node_id| Line::Node(*node_id))
+            .collect();
+        self.lines(
+            &lines,
+            self.compiler.get_span(root).end,
+            Spacing::Statements,
+        );
+
+        // Comments after the end of the file, if any
+        while self.next_comment < self.comments.len() {
+            self.newline();
+            self.comment(self.comments[self.next_comment]);
+        }
+
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+        self.output
+    }
+
+    fn node(&mut self, node_id: NodeId) {
+        match *self.compiler.get_node(node_id) {
+            AstNode::Int
+            | AstNode::Float
+            | AstNode::String
+            | AstNode::Name
+            | AstNode::Variable
+            | AstNode::True
+            | AstNode::False
+            | AstNode::Null
+            | AstNode::Pow
+            | AstNode::Multiply
+            | AstNode::Divide
+            | AstNode::FloorDiv
+            | AstNode::Modulo
+            | AstNode::Plus
+            | AstNode::Minus
+            | AstNode::Equal
+            | AstNode::NotEqual
+            | AstNode::LessThan
+            | AstNode::GreaterThan
+            | AstNode::LessThanOrEqual
+            | AstNode::GreaterThanOrEqual
+            | AstNode::RegexMatch
+            | AstNode::NotRegexMatch
+            | AstNode::In
+            | AstNode::NotIn
+            | AstNode::Has
+            | AstNode::NotHas
+            | AstNode::StartsWith
+            | AstNode::EndsWith
+            | AstNode::Append
+            | AstNode::BitAnd
+            | AstNode::BitXor
+            | AstNode::BitOr
+            | AstNode::ShiftLeft
+            | AstNode::ShiftRight
+            | AstNode::And
+            | AstNode::Xor
+            | AstNode::Or
+            | AstNode::Assignment
+            | AstNode::AddAssignment
+            | AstNode::SubtractAssignment
+            | AstNode::MultiplyAssignment
+            | AstNode::DivideAssignment
+            | AstNode::AppendAssignment
+            | AstNode::Break
+            | AstNode::Continue
+            | AstNode::FlagLong
+            | AstNode::FlagShort
+            | AstNode::FlagShortGroup
+            | AstNode::ImplicitIt
+            | AstNode::PatternWildcard
+            | AstNode::PatternBinding
+            | AstNode::Garbage => self.source(node_id),
+            AstNode::Type {
+                name,
+                args,
+                optional,
+            } => {
+                self.source(name);
+                if let Some(args) = args {
+                    self.node(args);
+                }
+                if optional {
+                    self.write("?");
+                }
+            }
+            AstNode::TypeArgs(_) => {
+                let args = self.compiler.get_type_args(node_id).args.clone();
+                self.write("<");
+                self.separated(&args, ", ", Self::node);
+                self.write(">");
+            }
+            AstNode::TypeParam { name, bound } => {
+                self.source(name);
+                if let Some(bound) = bound {
+                    self.write(": ");
+                    self.node(bound);
+                }
+            }
+            AstNode::RecordType {
+                fields, optional, ..
+            }
+            | AstNode::TableType {
+                fields, optional, ..
+            } => {
+                let is_table = matches!(self.compiler.get_node(node_id), AstNode::TableType { .. });
+                self.write(if is_table { "table" } else { "record" });
+                self.params(fields, "<", ">");
+                if optional {
+                    self.write("?");
+                }
+            }
+            AstNode::Let {
+                variable_name,
+                ty,
+                initializer,
+                is_mutable,
+            } => {
+                self.write(if is_mutable { "mut " } else { "let " });
+                self.source(variable_name);
+                if let Some(ty) = ty {
+                    self.write(": ");
+                    self.node(ty);
+                }
+                self.write(" = ");
+                self.node(initializer);
+            }
+            AstNode::While { condition, block } => {
+                self.write("while ");
+                self.condition(condition);
+                self.write(" ");
+                self.block(block);
+            }
+            AstNode::For {
+                variable,
+                range,
+                block,
+            } => {
+                self.write("for ");
+                self.source(variable);
+                self.write(" in ");
+                self.simple(range);
+                self.write(" ");
+                self.block(block);
+            }
+            AstNode::Loop { block } => {
+                self.write("loop ");
+                self.block(block);
+            }
+            AstNode::Return(value) => {
+                self.write("return");
+                if let Some(value) = value {
+                    self.write(" ");
+                    self.node(value);
+                }
+            }
+            AstNode::Def {
+                name,
+                type_params,
+                params,
+                in_out_types,
+                block,
+                env,
+                wrapped,
+            } => {
+                self.attributes(node_id);
+                self.write("def ");
+                if env {
+                    self.write("--env ");
+                }
+                if wrapped {
+                    self.write("--wrapped ");
+                }
+                self.source(name);
+                if let Some(type_params) = type_params {
+                    let type_params = self.compiler.get_params(type_params).nodes.clone();
+                    self.write("<");
+                    self.separated(&type_params, ", ", Self::node);
+                    self.write(">");
+                }
+                self.write(" ");
+                self.params(params, "[", "]");
+                if let Some(in_out_types) = in_out_types {
+                    self.write(": ");
+                    self.node(in_out_types);
+                }
+                self.write(" ");
+                self.block(block);
+            }
+            AstNode::Extern { name, params } => {
+                self.attributes(node_id);
+                self.write("extern ");
+                self.source(name);
+                self.write(" ");
+                self.params(params, "[", "]");
+            }
+            AstNode::Attribute { name, args } => {
+                self.write("@");
+                self.source(name);
+                for arg in self.compiler.lists[args.0].items.clone() {
+                    self.write(" ");
+                    self.simple(arg);
+                }
+            }
+            AstNode::Params(_) => self.params(node_id, "[", "]"),
+            AstNode::Param {
+                name,
+                ty,
+                default,
+                rest,
+            } => {
+                if rest {
+                    self.write("...");
+                }
+                self.source(name);
+                if let Some(ty) = ty {
+                    self.write(": ");
+                    self.node(ty);
+                }
+                if let Some(default) = default {
+                    self.write(" = ");
+                    self.simple(default);
+                }
+            }
+            AstNode::InOutTypes(_) => {
+                let types = self.compiler.get_in_out_types(node_id).nodes.clone();
+                if let [ty] = types[..] {
+                    self.node(ty);
+                } else {
+                    self.write("[");
+                    self.separated(&types, ", ", Self::node);
+                    self.write("]");
+                }
+            }
+            AstNode::InOutType(in_ty, out_ty) => {
+                self.node(in_ty);
+                self.write(" -> ");
+                self.node(out_ty);
+            }
+            AstNode::Closure { params, block } => self.closure(node_id, params, block),
+            AstNode::Alias { new_name, old_name } => {
+                self.write("alias ");
+                self.source(new_name);
+                self.write(" = ");
+                self.source(old_name);
+            }
+            AstNode::TypeAlias {
+                name,
+                type_params,
+                ty,
+            } => {
+                self.write("type ");
+                self.source(name);
+                if let Some(type_params) = type_params {
+                    let type_params = self.compiler.get_params(type_params).nodes.clone();
+                    self.write("<");
+                    self.separated(&type_params, ", ", Self::node);
+                    self.write(">");
+                }
+                self.write(" = ");
+                self.node(ty);
+            }
+            AstNode::Call(_) => {
+                let parts = self.compiler.get_call(node_id).parts.clone();
+                for (idx, part) in parts.into_iter().enumerate() {
+                    if idx > 0 {
+                        self.write(" ");
+                    }
+                    if let AstNode::Name = self.compiler.get_node(part) {
+                        // the name of the command and its subcommands
+                        self.source(part);
+                    } else {
+                        self.simple(part);
+                    }
+                }
+            }
+            AstNode::NamedValue { name, value } => {
+                self.source(name);
+                self.write("=");
+                self.simple(value);
+            }
+            AstNode::BinaryOp { lhs, op, rhs } => {
+                let precedence = self.compiler.get_node(op).precedence();
+                self.operand(lhs, precedence, false);
+                self.write(" ");
+                self.source(op);
+                self.write(" ");
+                if let AstNode::Assignment = self.compiler.get_node(op) {
+                    // the right-hand side of `=` may be a pipeline
+                    self.node(rhs);
+                } else {
+                    self.operand(rhs, precedence, true);
+                }
+            }
+            AstNode::Range { lhs, rhs } => {
+                self.simple(lhs);
+                self.write("..");
+                self.simple(rhs);
+            }
+            AstNode::PatternRange { lhs, rhs } => {
+                if let Some(lhs) = lhs {
+                    self.simple(lhs);
+                }
+                self.write("..");
+                if let Some(rhs) = rhs {
+                    self.simple(rhs);
+                }
+            }
+            AstNode::List(list_id) => {
+                let items = self.compiler.lists[list_id.0].items.clone();
+                let lines: Vec<_> = items.iter().map(|item| Line::Item(*item)).collect();
+                self.container(node_id, "[", &lines, "]", Spacing::Items);
+            }
+            AstNode::Table(_) => {
+                let table = self.compiler.get_table(node_id);
+                let lines: Vec<_> = std::iter::once(Line::TableHeader(table.header))
+                    .chain(table.rows.iter().map(|row| Line::Item(*row)))
+                    .collect();
+                self.container(node_id, "[", &lines, "]", Spacing::Items);
+            }
+            AstNode::Record(record_id) => {
+                let pairs = &self.compiler.records[record_id.0].pairs;
+                let lines: Vec<_> = pairs
+                    .iter()
+                    .map(|(key, value)| Line::Field(*key, *value))
+                    .collect();
+                self.container(node_id, "{", &lines, "}", Spacing::Fields);
+            }
+            AstNode::MemberAccess { target, field } => {
+                if !matches!(self.compiler.get_node(target), AstNode::ImplicitIt) {
+                    self.simple(target);
+                    self.write(".");
+                }
+                self.source(field);
+            }
+            AstNode::Block(_) => self.block(node_id),
+            AstNode::Pipeline(_) => self.pipeline(node_id),
+            AstNode::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.write("if ");
+                self.condition(condition);
+                self.write(" ");
+                self.block(then_block);
+                if let Some(else_block) = else_block {
+                    self.write(" else ");
+                    self.node(else_block);
+                }
+            }
+            AstNode::Try {
+                try_block,
+                catch_block,
+                finally_block,
+            } => {
+                self.write("try ");
+                self.block(try_block);
+                if let Some(catch_block) = catch_block {
+                    self.write(" catch ");
+                    self.block(catch_block);
+                }
+                if let Some(finally_block) = finally_block {
+                    self.write(" finally ");
+                    self.block(finally_block);
+                }
+            }
+            AstNode::Match(_) => {
+                let match_node = self.compiler.get_match(node_id);
+                let target = match_node.target;
+                let lines: Vec<_> = match_node
+                    .match_arms
+                    .iter()
+                    .map(|(pattern, result)| Line::Arm(*pattern, *result))
+                    .collect();
+
+                self.write("match ");
+                self.simple(target);
+                self.write(" {");
+                // match arms are always on separate lines
+                self.indent += 1;
+                self.newline();
+                self.lines(&lines, self.compiler.get_span(node_id).end, Spacing::Items);
+                self.indent -= 1;
+                self.newline();
+                self.write("}");
+            }
+            AstNode::Where(closure) => {
+                self.write("where ");
+                let AstNode::Closure { block, .. } = *self.compiler.get_node(closure) else {
+                    panic!("row condition without implicit closure");
+                };
+                let condition = self.compiler.get_block(block).nodes.clone();
+                self.separated(&condition, "; ", Self::node);
+            }
+            AstNode::EnvShorthand { name, value, expr } => {
+                self.source(name);
+                self.write("=");
+                self.source(value);
+                self.write(" ");
+                self.node(expr);
+            }
+            AstNode::Statement(node) => {
+                self.node(node);
+                self.write(";");
+            }
+            AstNode::PatternValue(value) => self.simple(value),
+            AstNode::PatternList(list_id) => {
+                let items = self.compiler.lists[list_id.0].items.clone();
+                self.write("[");
+                self.separated(&items, ", ", Self::node);
+                self.write("]");
+            }
+            AstNode::PatternRest(binding) => {
+                self.write("..");
+                if let Some(binding) = binding {
+                    self.source(binding);
+                }
+            }
+            AstNode::PatternRecord(record_id) => {
+                let pairs = self.compiler.records[record_id.0].pairs.clone();
+                self.write("{");
+                for (idx, (key, value)) in pairs.into_iter().enumerate() {
+                    if idx > 0 {
+                        self.write(", ");
+                    }
+                    self.source(key);
+                    self.write(": ");
+                    self.node(value);
+                }
+                self.write("}");
+            }
+            AstNode::PatternOr(list_id) => {
+                let alternatives = self.compiler.lists[list_id.0].items.clone();
+                self.separated(&alternatives, " | ", Self::node);
+            }
+            AstNode::PatternGuard { pattern, guard } => {
+                self.node(pattern);
+                self.write(" if ");
+                self.node(guard);
+            }
+        }
+    }
+
+    /// Formats the attributes of a `def` or `extern`, each on its own line
+    fn attributes(&mut self, node_id: NodeId) {
+        for attribute in self.compiler.get_attributes(node_id).to_vec() {
+            self.leading_comments(self.compiler.get_span(attribute).start);
+            self.node(attribute);
+            self.newline();
+        }
+        self.leading_comments(self.compiler.get_span(node_id).start);
+    }
+
+    /// Formats a node parsed as a simple expression, adding parentheses if it is not one
+    fn simple(&mut self, node_id: NodeId) {
+        if self.is_simple(node_id) {
+            self.node(node_id);
+        } else {
+            self.parenthesized(node_id);
+        }
+    }
+
+    /// Formats the condition of an `if` or `while`, which must not swallow the following block
+    fn condition(&mut self, node_id: NodeId) {
+        if self.is_simple(node_id)
+            || matches!(self.compiler.get_node(node_id), AstNode::BinaryOp { .. })
+        {
+            self.node(node_id);
+        } else {
+            self.parenthesized(node_id);
+        }
+    }
+
+    /// Formats an operand of a binary operator with the given precedence
+    fn operand(&mut self, node_id: NodeId, precedence: usize, is_rhs: bool) {
+        let needs_parens = match self.compiler.get_node(node_id) {
+            AstNode::BinaryOp { op, .. } => {
+                // operators with the same precedence are left-associative
+                let op_precedence = self.compiler.get_node(*op).precedence();
+                op_precedence < precedence || (is_rhs && op_precedence == precedence)
+            }
+            _ => !self.is_simple(node_id),
+        };
+
+        if needs_parens {
+            self.parenthesized(node_id);
+        } else {
+            self.node(node_id);
+        }
+    }
+
+    fn parenthesized(&mut self, node_id: NodeId) {
+        self.write("(");
+        self.node(node_id);
+        self.write(")");
+    }
+
+    /// Whether the node can be parsed as a simple expression without parentheses
+    fn is_simple(&self, node_id: NodeId) -> bool {
+        matches!(
+            self.compiler.get_node(node_id),
+            AstNode::Int
+                | AstNode::Float
+                | AstNode::String
+                | AstNode::Name
+                | AstNode::Variable
+                | AstNode::True
+                | AstNode::False
+                | AstNode::Null
+                | AstNode::List(_)
+                | AstNode::Table(_)
+                | AstNode::Record(_)
+                | AstNode::Closure { .. }
+                | AstNode::MemberAccess { .. }
+                | AstNode::Range { .. }
+                | AstNode::Garbage
+        )
+    }
+
+    /// Formats a block wrapped in curly braces, e.g., the body of a `def`
+    fn block(&mut self, node_id: NodeId) {
+        let span = self.compiler.get_span(node_id);
+        let statements = self.compiler.get_block(node_id).nodes.clone();
+
+        if statements.is_empty() && !self.has_comments(span.start, span.end) {
+            self.write("{}");
+            return;
+        }
+
+        let is_flat = statements.len() == 1
+            && self.try_flat(|f| {
+                f.fail_on_comments(span.start, span.end);
+                f.write("{ ");
+                f.node(statements[0]);
+                f.write(" }");
+            });
+
+        if !is_flat {
+            self.write("{");
+            self.statements(node_id);
+            self.write("}");
+        }
+    }
+
+    fn closure(&mut self, node_id: NodeId, params: Option<NodeId>, block: NodeId) {
+        let span = self.compiler.get_span(node_id);
+        let statements = self.compiler.get_block(block).nodes.clone();
+
+        let is_flat = statements.len() <= 1
+            && self.try_flat(|f| {
+                f.fail_on_comments(span.start, span.end);
+                f.write("{");
+                if let Some(params) = params {
+                    f.params(params, "|", "|");
+                }
+                f.write(" ");
+                if let [statement] = statements[..] {
+                    f.node(statement);
+                    f.write(" ");
+                }
+                f.write("}");
+            });
+
+        if !is_flat {
+            self.write("{");
+            if let Some(params) = params {
+                self.params(params, "|", "|");
+            }
+            self.statements(block);
+            self.write("}");
+        }
+    }
+
+    /// Formats the statements of a block on separate, indented lines
+    fn statements(&mut self, block: NodeId) {
+        let lines: Vec<_> = self
+            .compiler
+            .get_block(block)
+            .nodes
+            .iter()
+            .map(|node_id| Line::Node(*node_id))
+            .collect();
+
+        self.indent += 1;
+        self.newline();
+        self.lines(
+            &lines,
+            self.compiler.get_span(block).end,
+            Spacing::Statements,
+        );
+        self.indent -= 1;
+        self.newline();
+    }
+
+    /// Formats parameters, or record type fields, between the given delimiters
+    fn params(&mut self, node_id: NodeId, open: &str, close: &str) {
+        let span = self.compiler.get_span(node_id);
+        let params = self.compiler.get_params(node_id).nodes.clone();
+
+        let is_flat = self.try_flat(|f| {
+            f.fail_on_comments(span.start, span.end);
+            f.write(open);
+            f.separated(&params, ", ", Self::node);
+            f.write(close);
+        });
+
+        if !is_flat {
+            let lines: Vec<_> = params.iter().map(|param| Line::Node(*param)).collect();
+            self.write(open);
+            self.indent += 1;
+            self.newline();
+            self.lines(&lines, span.end, Spacing::Params);
+            self.indent -= 1;
+            self.newline();
+            self.write(close);
+        }
+    }
+
+    /// Formats a list, table or record, on one line if it fits or with one item per line
+    fn container(
+        &mut self,
+        node_id: NodeId,
+        open: &str,
+        lines: &[Line],
+        close: &str,
+        spacing: Spacing,
+    ) {
+        if lines.is_empty() {
+            self.write(open);
+            self.write(close);
+            return;
+        }
+
+        let span = self.compiler.get_span(node_id);
+        let is_flat = self.try_flat(|f| {
+            if spacing != Spacing::Items {
+                f.fail_on_comments(span.start, span.end);
+            }
+            f.write(open);
+            for (idx, line) in lines.iter().enumerate() {
+                if idx > 0 && !matches!(lines[idx - 1], Line::TableHeader(_)) {
+                    f.write(",");
+                }
+                if idx > 0 {
+                    f.write(" ");
+                }
+                f.line(*line);
+            }
+            f.write(close);
+        });
+
+        if !is_flat {
+            self.write(open);
+            self.indent += 1;
+            self.newline();
+            self.lines(lines, span.end, spacing);
+            self.indent -= 1;
+            self.newline();
+            self.write(close);
+        }
+    }
+
+    /// Formats a pipeline on one line if it fits or with one element per line
+    fn pipeline(&mut self, node_id: NodeId) {
+        let AstNode::Pipeline(pipeline_id) = *self.compiler.get_node(node_id) else {
+            panic!("internal error: pipeline is not a pipeline");
+        };
+        let elements = self.compiler.pipelines[pipeline_id.0]
+            .get_expressions()
+            .clone();
+        let span_start = self.compiler.get_span(node_id).start;
+        let last_start = self.compiler.get_span(elements[elements.len() - 1]).start;
+
+        let is_flat = self.try_flat(|f| {
+            // comments can only be kept before an element, not after the last one
+            f.fail_on_comments(span_start, last_start);
+            f.separated(&elements, " | ", Self::node);
+        });
+
+        if !is_flat {
+            self.node(elements[0]);
+            self.indent += 1;
+            for element in &elements[1..] {
+                self.write(" |");
+                self.newline();
+                self.leading_comments(self.compiler.get_span(*element).start);
+                self.node(*element);
+            }
+            self.indent -= 1;
+        }
+    }
+
+    /// Formats lines of a construct split into multiple lines with the comments between them.
+    ///
+    /// Must be called at the start of a line. The remaining comments before `end` are printed after
+    /// the last line, unless the lines cannot contain comments.
+    fn lines(&mut self, lines: &[Line], end: usize, spacing: Spacing) {
+        // Source position where the previously printed line or comment ended
+        let mut prev_end = None;
+
+        for line in lines {
+            let (start, line_end) = self.line_span(*line);
+
+            if spacing != Spacing::Items {
+                while let Some(comment) = self.comment_before(start) {
+                    self.separate(prev_end, comment.start, spacing);
+                    self.comment(comment);
+                    prev_end = Some(comment.end);
+                }
+            }
+
+            self.separate(prev_end, start, spacing);
+            self.line(*line);
+            prev_end = Some(line_end);
+
+            // Comments on the same line and, for statements, comments inside of the statement that
+            // could not be printed where they were
+            let mut is_first = true;
+            while let Some(&comment) = self.comments.get(self.next_comment) {
+                let is_on_same_line = comment.start >= line_end
+                    && !self.compiler.source[line_end..comment.start].contains(&b'\n');
+                let is_inside = comment.start < line_end && spacing == Spacing::Statements;
+                if !is_on_same_line && !is_inside {
+                    break;
+                }
+
+                if is_first {
+                    self.write(" ");
+                } else {
+                    self.newline();
+                }
+                self.comment(comment);
+                prev_end = Some(comment.end.max(line_end));
+                is_first = false;
+            }
+        }
+
+        if spacing != Spacing::Items {
+            while let Some(comment) = self.comment_before(end) {
+                self.separate(prev_end, comment.start, spacing);
+                self.comment(comment);
+                prev_end = Some(comment.end);
+            }
+        }
+    }
+
+    fn line(&mut self, line: Line) {
+        match line {
+            Line::Node(node_id) => self.node(node_id),
+            Line::Item(node_id) => self.simple(node_id),
+            Line::TableHeader(node_id) => {
+                self.node(node_id);
+                self.write(";");
+            }
+            Line::Field(key, value) => {
+                self.simple(key);
+                self.write(": ");
+                self.simple(value);
+            }
+            Line::Arm(pattern, result) => {
+                self.node(pattern);
+                self.write(" => ");
+                self.node(result);
+            }
+        }
+    }
+
+    /// Source span of a line, including the attributes of a definition
+    fn line_span(&self, line: Line) -> (usize, usize) {
+        let (first, last) = match line {
+            Line::Node(node_id) | Line::Item(node_id) | Line::TableHeader(node_id) => {
+                let first = match self.compiler.get_node(node_id) {
+                    AstNode::Def { .. } | AstNode::Extern { .. } => self
+                        .compiler
+                        .get_attributes(node_id)
+                        .first()
+                        .copied()
+                        .unwrap_or(node_id),
+                    _ => node_id,
+                };
+                (first, node_id)
+            }
+            Line::Field(key, value) | Line::Arm(key, value) => (key, value),
+        };
+
+        (
+            self.compiler.get_span(first).start,
+            self.compiler.get_span(last).end,
+        )
+    }
+
+    /// Starts a new line after the previous line, keeping a blank line between statements
+    fn separate(&mut self, prev_end: Option<usize>, start: usize, spacing: Spacing) {
+        let Some(prev_end) = prev_end else {
+            return;
+        };
+
+        self.newline();
+        if spacing == Spacing::Statements && prev_end < start {
+            let between = &self.compiler.source[prev_end..start];
+            if between.iter().filter(|c| **c == b'\n').count() > 1 {
+                self.newline();
+            }
+        }
+    }
+
+    /// Prints the comments before `position` on separate lines
+    fn leading_comments(&mut self, position: usize) {
+        while let Some(comment) = self.comment_before(position) {
+            self.comment(comment);
+            self.newline();
+        }
+    }
+
+    /// The next comment to print if it starts before `position`
+    fn comment_before(&self, position: usize) -> Option<Span> {
+        self.comments
+            .get(self.next_comment)
+            .filter(|comment| comment.start < position)
+            .copied()
+    }
+
+    fn comment(&mut self, comment: Span) {
+        let text = self
+            .compiler
+            .get_span_contents_manual(comment.start, comment.end);
+        let text = String::from_utf8_lossy(text);
+        self.write(text.trim_end());
+        self.next_comment += 1;
+    }
+
+    /// Whether there are comments left to print between the two positions
+    fn has_comments(&self, start: usize, end: usize) -> bool {
+        self.comments[self.next_comment..]
+            .iter()
+            .take_while(|comment| comment.start < end)
+            .any(|comment| comment.start >= start)
+    }
+
+    /// Prevent printing on a single line if there are comments, which must end a line
+    fn fail_on_comments(&mut self, start: usize, end: usize) {
+        if self.has_comments(start, end) {
+            self.flat_failed = true;
+        }
+    }
+
+    /// Tries to print something on a single line, reverting the output if it does not fit
+    fn try_flat(&mut self, print: impl FnOnce(&mut Self)) -> bool {
+        if self.flat {
+            // already printing an enclosing node on a single line
+            print(self);
+            return true;
+        }
+
+        let output_len = self.output.len();
+        let pending_indent = self.pending_indent;
+
+        self.flat = true;
+        self.flat_failed = false;
+        print(self);
+        self.flat = false;
+
+        if !self.flat_failed && self.column() <= MAX_WIDTH {
+            return true;
+        }
+
+        self.output.truncate(output_len);
+        self.pending_indent = pending_indent;
+        false
+    }
+
+    /// Prints nodes with a separator between them
+    fn separated(&mut self, nodes: &[NodeId], separator: &str, print: fn(&mut Self, NodeId)) {
+        for (idx, node_id) in nodes.iter().enumerate() {
+            if idx > 0 {
+                self.write(separator);
+            }
+            print(self, *node_id);
+        }
+    }
+
+    /// Prints the source of a node as it is, e.g., for names and literals
+    fn source(&mut self, node_id: NodeId) {
+        let text = String::from_utf8_lossy(self.compiler.get_span_contents(node_id));
+        self.write(&text);
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.pending_indent {
+            self.output
+                .push_str(&" ".repeat(self.indent * INDENT_WIDTH));
+            self.pending_indent = false;
+        }
+        self.output.push_str(text);
+    }
+
+    fn newline(&mut self) {
+        if self.flat {
+            self.flat_failed = true;
+        }
+        self.output.push('\n');
+        self.pending_indent = true;
+    }
+
+    /// Width of the current line
+    fn column(&self) -> usize {
+        let line_start = self.output.rfind('\n').map_or(0, |idx| idx + 1);
+        self.output[line_start..].chars().count()
+    }
+}
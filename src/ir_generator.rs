@@ -1,9 +1,12 @@
 use crate::compiler::Compiler;
 use crate::errors::{Severity, SourceError};
 use crate::parser::{AstNode, NodeId};
-use nu_protocol::ast::{Math, Operator};
+use crate::typechecker::{FORBIDDEN_TYPE, UNKNOWN_TYPE};
+use crate::visitor::children;
+use nu_protocol::ast::{Bits, Boolean, Comparison, Math, Operator};
 use nu_protocol::ir::{Instruction, IrBlock, Literal};
-use nu_protocol::{RegId, Span};
+use nu_protocol::RegId;
+use serde_json::{json, Value};
 
 /// Generates IR (Intermediate Representation) from nu AST.
 pub struct IrGenerator<'a> {
@@ -30,13 +33,12 @@ impl<'a> IrGenerator<'a> {
         }
     }
 
-    /// Generates the IR from the given state of the compiler.
+    /// Generates the IR of the last added file from the given state of the compiler.
     /// After this is called, use `block` and `errors` to get the result.
     pub fn generate(&mut self) {
-        if self.compiler.ast_nodes.is_empty() {
+        let Some(node_id) = self.compiler.files.last().and_then(|file| file.root) else {
             return;
-        }
-        let node_id = NodeId(self.compiler.ast_nodes.len() - 1);
+        };
         let Some(reg) = self.generate_node(node_id) else {
             return;
         };
@@ -78,15 +80,35 @@ impl<'a> IrGenerator<'a> {
         if !self.errors.is_empty() {
             result.push_str("==== IR ERRORS ====\n");
             for error in &self.errors {
-                result.push_str(&format!(
-                    "{:?} (NodeId {}): {}\n",
-                    error.severity, error.node_id.0, error.message
-                ));
+                result.push_str(&format!("{}\n", error.display(self.compiler)));
             }
         }
         result
     }
 
+    /// Serializes the generated IR and errors.
+    pub fn to_json(&self) -> Value {
+        let instructions: Vec<_> = self
+            .block
+            .instructions
+            .iter()
+            .map(|instruction| {
+                serde_json::to_value(instruction).expect("IR instructions are serializable")
+            })
+            .collect();
+
+        json!({
+            "register_count": self.block.register_count,
+            "file_count": self.block.file_count,
+            "instructions": instructions,
+            "errors": self
+                .errors
+                .iter()
+                .map(|error| error.to_json(self.compiler))
+                .collect::<Vec<_>>(),
+        })
+    }
+
     // Returns unused register.
     fn next_register(&mut self) -> RegId {
         let r = RegId::new(self.block.register_count);
@@ -109,6 +131,18 @@ impl<'a> IrGenerator<'a> {
                 );
                 Some(next_reg)
             }
+            AstNode::True | AstNode::False => {
+                let next_reg = self.next_register();
+                let val = matches!(ast_node, AstNode::True);
+                self.add_instruction(
+                    node_id,
+                    Instruction::LoadLiteral {
+                        dst: next_reg,
+                        lit: Literal::Bool(val),
+                    },
+                );
+                Some(next_reg)
+            }
             AstNode::Block(_) => {
                 let block = &self.compiler.get_block(node_id);
                 let mut last = None;
@@ -118,9 +152,19 @@ impl<'a> IrGenerator<'a> {
                 }
                 last
             }
+            AstNode::BinaryOp { lhs, op, rhs }
+                if matches!(self.compiler.get_node(*op), AstNode::And | AstNode::Or) =>
+            {
+                let is_and = matches!(self.compiler.get_node(*op), AstNode::And);
+                self.generate_short_circuit(node_id, *lhs, is_and, *rhs)
+            }
             AstNode::BinaryOp { lhs, op, rhs } => {
-                let l = self.generate_node(*lhs)?;
-                let r = self.generate_node(*rhs)?;
+                let mut l = self.generate_node(*lhs)?;
+                let mut r = self.generate_node(*rhs)?;
+                if let AstNode::Has | AstNode::NotHas = self.compiler.get_node(*op) {
+                    // `a has b` is evaluated as `b in a`
+                    std::mem::swap(&mut l, &mut r);
+                }
                 let o = self.node_to_operator(*op)?;
                 self.add_instruction(
                     node_id,
@@ -134,25 +178,111 @@ impl<'a> IrGenerator<'a> {
             }
             _ => {
                 self.error(format!("node {:?} not suported yet", ast_node), node_id);
+                // Report the unsupported nodes inside it as well. Nodes without a type, such as
+                // names and parameters, are not evaluated on their own.
+                for child in children(self.compiler, node_id) {
+                    if ![UNKNOWN_TYPE, FORBIDDEN_TYPE].contains(&self.compiler.node_types[child.0])
+                    {
+                        self.generate_node(child);
+                    }
+                }
                 None
             }
         }
     }
 
-    fn add_instruction(&mut self, node_id: NodeId, instruction: Instruction) {
-        let span = self.compiler.get_span(node_id);
-        self.block.spans.push(Span {
-            start: span.start,
-            end: span.end,
-        });
+    /// Generates `lhs and rhs` or `lhs or rhs`, evaluating `rhs` only if `lhs` does not already
+    /// decide the result
+    fn generate_short_circuit(
+        &mut self,
+        node_id: NodeId,
+        lhs: NodeId,
+        is_and: bool,
+        rhs: NodeId,
+    ) -> Option<RegId> {
+        let l = self.generate_node(lhs)?;
+
+        // Skip the right-hand side if `lhs` is false (`and`) or true (`or`)
+        let branch = self.add_instruction(node_id, Instruction::BranchIf { cond: l, index: 0 });
+        let short_circuit = if is_and {
+            let jump = self.add_instruction(node_id, Instruction::Jump { index: 0 });
+            self.patch_jump(branch, self.next_index());
+            jump
+        } else {
+            branch
+        };
+        let r = self.generate_node(rhs)?;
+        self.add_instruction(node_id, Instruction::Move { dst: l, src: r });
+        let end = self.add_instruction(node_id, Instruction::Jump { index: 0 });
+
+        // `BranchIf` takes its condition out of the register, so the short-circuit value, which is
+        // the result, must be loaded again
+        self.patch_jump(short_circuit, self.next_index());
+        self.add_instruction(
+            node_id,
+            Instruction::LoadLiteral {
+                dst: l,
+                lit: Literal::Bool(!is_and),
+            },
+        );
+        self.patch_jump(end, self.next_index());
+
+        Some(l)
+    }
+
+    /// Index of the next instruction to be added
+    fn next_index(&self) -> usize {
+        self.block.instructions.len()
+    }
+
+    /// Sets the target of a previously added jump or branch instruction
+    fn patch_jump(&mut self, instruction_index: usize, target: usize) {
+        match &mut self.block.instructions[instruction_index] {
+            Instruction::Jump { index } | Instruction::BranchIf { index, .. } => *index = target,
+            instruction => panic!("cannot patch jump target of {:?}", instruction),
+        }
+    }
+
+    /// Adds an instruction and returns its index
+    fn add_instruction(&mut self, node_id: NodeId, instruction: Instruction) -> usize {
+        self.block
+            .spans
+            .push(self.compiler.get_span(node_id).into());
         self.block.ast.push(None);
         self.block.instructions.push(instruction);
+        self.block.instructions.len() - 1
     }
 
     fn node_to_operator(&mut self, node_id: NodeId) -> Option<Operator> {
         match self.compiler.get_node(node_id) {
             AstNode::Plus => Some(Operator::Math(Math::Plus)),
+            AstNode::Minus => Some(Operator::Math(Math::Minus)),
             AstNode::Multiply => Some(Operator::Math(Math::Multiply)),
+            AstNode::Divide => Some(Operator::Math(Math::Divide)),
+            AstNode::FloorDiv => Some(Operator::Math(Math::FloorDivision)),
+            AstNode::Modulo => Some(Operator::Math(Math::Modulo)),
+            AstNode::Pow => Some(Operator::Math(Math::Pow)),
+            AstNode::Append => Some(Operator::Math(Math::Concat)),
+            AstNode::Equal => Some(Operator::Comparison(Comparison::Equal)),
+            AstNode::NotEqual => Some(Operator::Comparison(Comparison::NotEqual)),
+            AstNode::LessThan => Some(Operator::Comparison(Comparison::LessThan)),
+            AstNode::GreaterThan => Some(Operator::Comparison(Comparison::GreaterThan)),
+            AstNode::LessThanOrEqual => Some(Operator::Comparison(Comparison::LessThanOrEqual)),
+            AstNode::GreaterThanOrEqual => {
+                Some(Operator::Comparison(Comparison::GreaterThanOrEqual))
+            }
+            AstNode::RegexMatch => Some(Operator::Comparison(Comparison::RegexMatch)),
+            AstNode::NotRegexMatch => Some(Operator::Comparison(Comparison::NotRegexMatch)),
+            AstNode::In | AstNode::Has => Some(Operator::Comparison(Comparison::In)),
+            AstNode::NotIn | AstNode::NotHas => Some(Operator::Comparison(Comparison::NotIn)),
+            AstNode::StartsWith => Some(Operator::Comparison(Comparison::StartsWith)),
+            AstNode::EndsWith => Some(Operator::Comparison(Comparison::EndsWith)),
+            AstNode::BitAnd => Some(Operator::Bits(Bits::BitAnd)),
+            AstNode::BitXor => Some(Operator::Bits(Bits::BitXor)),
+            AstNode::BitOr => Some(Operator::Bits(Bits::BitOr)),
+            AstNode::ShiftLeft => Some(Operator::Bits(Bits::ShiftLeft)),
+            AstNode::ShiftRight => Some(Operator::Bits(Bits::ShiftRight)),
+            AstNode::Xor => Some(Operator::Boolean(Boolean::Xor)),
             node => {
                 self.error(format!("unrecognized operator {:?}", node), node_id);
                 None
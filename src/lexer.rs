@@ -1,5 +1,6 @@
 use crate::compiler::{Span, Spanned};
 use logos::{Lexer, Logos};
+use serde_json::{json, Value};
 
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
 pub enum LexError {
@@ -92,6 +93,21 @@ impl Tokens {
         self.spans[self.pos]
     }
 
+    /// Return the token before the current position, if any
+    pub fn previous_token(&self) -> Option<Token> {
+        self.pos.checked_sub(1).map(|pos| self.tokens[pos])
+    }
+
+    /// Return the spans of all comments, e.g., for keeping them when formatting the code
+    pub fn comments(&self) -> Vec<Span> {
+        self.tokens
+            .iter()
+            .zip(self.spans.iter())
+            .filter(|(token, _)| **token == Token::Comment)
+            .map(|(_, span)| *span)
+            .collect()
+    }
+
     // Printing
 
     /// Format the tokens into a human-readable output for debugging
@@ -120,6 +136,25 @@ impl Tokens {
         result
     }
 
+    /// Serialize the tokens with their spans and source text
+    pub fn to_json(&self, source: &[u8]) -> Value {
+        self.tokens
+            .iter()
+            .zip(self.spans.iter())
+            .map(|(token, span)| {
+                json!({
+                    "token": format!("{:?}", token),
+                    "span": span,
+                    "text": String::from_utf8_lossy(
+                        source
+                            .get(span.start..span.end)
+                            .expect("missing source of token span")
+                    ),
+                })
+            })
+            .collect()
+    }
+
     /// Print the output of display() to standard output
     pub fn print(&self, source: &[u8]) {
         let output = self.display(source);
@@ -249,6 +284,10 @@ fn lex_internal(
                 span_offset + span.start,
                 tokens,
             )?,
+            Ok(Token::IntDotDot) => {
+                tokens.push(Token::Int, Span::new(new_span.start, new_span.end - 2));
+                tokens.push(Token::DotDot, Span::new(new_span.end - 2, new_span.end));
+            }
             Ok(token) => tokens.push(token, new_span),
             Err(e) => {
                 return Err(Spanned::new(e, new_span));
@@ -314,6 +353,12 @@ pub enum Token {
     Int,
     #[regex(r"([0-9][0-9_]*)*\.([0-9][0-9_]*)*([eE][+-]?[0-9_]+)?")]
     Float,
+    /// Integer followed by '..', e.g., the start of a range 1..10
+    ///
+    /// The token is split into Int and DotDot and is not actually present in the result. Without it,
+    /// the integer would be lexed as a float (such as `1.`).
+    #[regex("(0[xob])?[0-9][0-9_]*\\.\\.")]
+    IntDotDot,
     #[regex("\n|\r\n|\x0C")]
     Newline,
     #[regex(r#""([^"\\]|\\["\\bnfrt])*""#)]
@@ -566,6 +611,20 @@ mod test {
         test_lex(b"", &[(Token::Eof, span(0, 0))], Ok(()));
     }
 
+    #[test]
+    fn lex_int_range() {
+        test_lex(
+            b"1..10",
+            &[
+                (Token::Int, span(0, 1)),
+                (Token::DotDot, span(1, 3)),
+                (Token::Int, span(3, 5)),
+                (Token::Eof, span(5, 5)),
+            ],
+            Ok(()),
+        );
+    }
+
     #[test]
     fn lex_unmatched_string() {
         // TODO: Make unmatched delimiters nicer
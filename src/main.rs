@@ -1,24 +1,54 @@
 use std::process::exit;
 
-use new_nu_parser::compiler::Compiler;
+use new_nu_parser::compiler::{Compiler, JSON_FORMAT_VERSION};
+use new_nu_parser::errors::Severity;
+use new_nu_parser::formatter::Formatter;
 use new_nu_parser::ir_generator::IrGenerator;
 use new_nu_parser::lexer::lex;
 use new_nu_parser::parser::Parser;
 use new_nu_parser::resolver::Resolver;
 use new_nu_parser::typechecker::Typechecker;
+use serde_json::{json, Value};
+
+/// Print the JSON output of all files processed so far, when it was requested with `--json`
+fn print_json(json_files: Option<Vec<Value>>) {
+    if let Some(files) = json_files {
+        let output = json!({ "version": JSON_FORMAT_VERSION, "files": files });
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&output).expect("JSON values are serializable")
+        );
+    }
+}
 
 fn main() {
     let mut compiler = Compiler::new();
     let mut do_print = true;
+    let mut do_print_tree = false;
+    let mut do_format = false;
+    // Outputs of the files when printing JSON instead of text
+    let mut json_files = None;
+    // Whether any file had errors. Later files are still compiled so that all errors are reported.
+    let mut failed = false;
 
     for arg in std::env::args().skip(1) {
         if arg == "--no-print" {
             do_print = false;
+        } else if arg == "--json" {
+            json_files = Some(vec![]);
+        } else if arg == "--tree" {
+            do_print_tree = true;
+        } else if arg == "--format" {
+            do_format = true;
         }
     }
 
+    if json_files.is_some() || do_print_tree || do_format {
+        do_print = false;
+    }
+
     for fname in std::env::args().skip(1) {
-        if fname == "--no-print" {
+        if ["--no-print", "--json", "--tree", "--format"].contains(&fname.as_str()) {
             continue;
         }
 
@@ -30,13 +60,14 @@ fn main() {
         };
 
         let span_offset = compiler.span_offset();
-        compiler.add_file(&fname, &contents);
+        let file_id = compiler.add_file(&fname, &contents);
 
         let (tokens, err) = lex(&contents, span_offset);
         if let Err(e) = err {
             tokens.print(&compiler.source);
             eprintln!(
-                "Lexing error. Error: {:?}, '{}'",
+                "{}: Lexing error. Error: {:?}, '{}'",
+                compiler.display_location(e.span.start),
                 e,
                 String::from_utf8_lossy(
                     compiler.get_span_contents_manual(e.span.start, e.span.end)
@@ -48,45 +79,110 @@ fn main() {
         if do_print {
             tokens.print(&compiler.source);
         }
+        let tokens_json = json_files
+            .as_ref()
+            .map(|_| tokens.to_json(&compiler.source));
+
+        // errors of earlier files were already reported
+        let num_errors = compiler.errors.len();
+        let has_file_errors = |compiler: &Compiler| {
+            compiler.errors[num_errors..]
+                .iter()
+                .any(|error| error.severity == Severity::Error)
+        };
 
+        let comments = tokens.comments();
         let parser = Parser::new(compiler, tokens);
 
         compiler = parser.parse();
 
-        if do_print {
-            compiler.print();
+        if do_format {
+            if has_file_errors(&compiler) {
+                for error in &compiler.errors[num_errors..] {
+                    eprintln!("{}", error.display(&compiler));
+                }
+                failed = true;
+                continue;
+            }
+            let root = compiler.files[file_id.0]
+                .root
+                .expect("parsed files have a root block");
+            print!("{}", Formatter::new(&compiler, comments).format(root));
+            continue;
         }
 
-        if !compiler.errors.is_empty() {
-            exit(1);
+        if do_print {
+            compiler.print();
         }
 
-        let mut resolver = Resolver::new(&compiler);
+        let name_bindings = compiler.take_name_bindings();
+        let mut resolver = Resolver::new(&compiler, name_bindings);
         resolver.resolve();
 
         if do_print {
             resolver.print();
         }
+        let names_json = json_files.as_ref().map(|_| resolver.to_json());
 
         compiler.merge_name_bindings(resolver.to_name_bindings());
 
-        if !compiler.errors.is_empty() {
-            exit(1);
-        }
+        // Typecheck even when there are errors so that every independent error is reported
+        let is_well_formed = !has_file_errors(&compiler);
 
-        let mut typechecker = Typechecker::new(&compiler);
+        let types = compiler.take_types();
+        let mut typechecker = Typechecker::new(&compiler, types);
         typechecker.typecheck();
 
         if do_print {
             typechecker.print();
         }
+        let types_json = json_files.as_ref().map(|_| typechecker.to_json());
 
-        let mut ir_generator = IrGenerator::new(&compiler);
-        ir_generator.generate();
-        if do_print {
-            ir_generator.print();
+        if do_print_tree {
+            let root = compiler.files[file_id.0]
+                .root
+                .expect("parsed files have a root block");
+            print!("{}", compiler.display_tree(root, Some(&typechecker)));
         }
 
         compiler.merge_types(typechecker.to_types());
+
+        if do_print_tree {
+            for error in &compiler.errors[num_errors..] {
+                println!("{}", error.display(&compiler));
+            }
+        }
+
+        // IR can only be generated from well-formed and resolved code
+        let ir_json = if is_well_formed {
+            let mut ir_generator = IrGenerator::new(&compiler);
+            ir_generator.generate();
+            if do_print {
+                ir_generator.print();
+            }
+            json_files.as_ref().map(|_| ir_generator.to_json())
+        } else {
+            None
+        };
+
+        if let Some(files) = &mut json_files {
+            files.push(json!({
+                "file": fname,
+                "tokens": tokens_json,
+                "ast": compiler.file_to_json(file_id),
+                "names": names_json,
+                "types": types_json,
+                "ir": ir_json,
+            }));
+        }
+
+        if !is_well_formed {
+            failed = true;
+        }
+    }
+
+    print_json(json_files);
+    if failed {
+        exit(1);
     }
 }
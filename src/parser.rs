@@ -2,6 +2,7 @@ use crate::compiler::{Compiler, RollbackPoint, Span};
 use crate::errors::{Severity, SourceError};
 use crate::lexer::{Token, Tokens};
 
+use serde::Serialize;
 use tracy_client::span;
 
 pub struct Parser {
@@ -9,37 +10,37 @@ pub struct Parser {
     tokens: Tokens,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub struct NodeId(pub usize);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub struct BlockId(pub usize);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub struct ParamsId(pub usize);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub struct InOutTypesId(pub usize);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub struct CallId(pub usize);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub struct ListId(pub usize);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub struct TableId(pub usize);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub struct RecordId(pub usize);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub struct MatchId(pub usize);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub struct TypeArgsId(pub usize);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub struct PipelineId(pub usize);
 
 #[derive(Debug, Clone)]
@@ -189,12 +190,14 @@ pub enum ParamsContext {
     Angles,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BarewordContext {
     /// Bareword is a string (e.g., in a list)
     String,
     /// Bareword is a name (e.g., in a call position)
     Call,
+    /// Bareword is a field of the current row (e.g., `size` in `where size > 10`)
+    RowCondition,
 }
 
 enum AssignmentOrExpression {
@@ -210,7 +213,7 @@ impl AssignmentOrExpression {
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
 pub enum AstNode {
     Int,
     Float,
@@ -222,7 +225,23 @@ pub enum AstNode {
         optional: bool,
     },
     TypeArgs(TypeArgsId),
+    /// Type parameter of a generic definition
+    TypeParam {
+        name: NodeId,
+        /// Upper bound of the type parameter, e.g., `number` in `T: number`
+        bound: Option<NodeId>,
+    },
     RecordType {
+        /// The `record` keyword
+        name: NodeId,
+        /// Contains [AstNode::Params]
+        fields: NodeId,
+        optional: bool,
+    },
+    /// Table type such as `table<a: int>`, i.e., a list of records
+    TableType {
+        /// The `table` keyword
+        name: NodeId,
         /// Contains [AstNode::Params]
         fields: NodeId,
         optional: bool,
@@ -253,7 +272,17 @@ pub enum AstNode {
     RegexMatch,
     NotRegexMatch,
     In,
+    NotIn,
+    Has,
+    NotHas,
+    StartsWith,
+    EndsWith,
     Append,
+    BitAnd,
+    BitXor,
+    BitOr,
+    ShiftLeft,
+    ShiftRight,
     And,
     Xor,
     Or,
@@ -303,10 +332,19 @@ pub enum AstNode {
         name: NodeId,
         params: NodeId,
     },
+    /// Attribute of the following `def`/`extern`, e.g., `@example "description" { code }`
+    Attribute {
+        name: NodeId,
+        args: ListId,
+    },
     Params(ParamsId),
     Param {
         name: NodeId,
         ty: Option<NodeId>,
+        /// Default value making the parameter optional
+        default: Option<NodeId>,
+        /// Rest parameter (`...rest`) collecting all remaining arguments
+        rest: bool,
     },
     InOutTypes(InOutTypesId),
     /// Input/output type pair for a command
@@ -319,6 +357,12 @@ pub enum AstNode {
         new_name: NodeId,
         old_name: NodeId,
     },
+    /// Type alias declaration, e.g., `type Pair<T> = list<T>`
+    TypeAlias {
+        name: NodeId,
+        type_params: Option<NodeId>,
+        ty: NodeId,
+    },
 
     /// Long flag ('--' + one or more letters)
     FlagLong,
@@ -362,7 +406,43 @@ pub enum AstNode {
         finally_block: Option<NodeId>,
     },
     Match(MatchId),
+    /// Row condition `where cond`, holding an implicit closure evaluating `cond` for each row
+    Where(NodeId),
+    /// Implicit `$it` of a field shorthand in a row condition, e.g., `size` meaning `$it.size`
+    ImplicitIt,
+    /// Environment variable shorthand `FOO=bar expr` setting `FOO` only while evaluating `expr`
+    EnvShorthand {
+        name: NodeId,
+        value: NodeId,
+        expr: NodeId,
+    },
     Statement(NodeId),
+
+    // Match patterns
+    /// Wildcard pattern `_` matching any value
+    PatternWildcard,
+    /// Pattern `$name` matching any value and binding it to a new variable
+    PatternBinding,
+    /// Pattern matching a literal value, e.g., `1` or `"foo"`
+    PatternValue(NodeId),
+    /// Pattern matching a range of numbers, e.g., `1..10`, `4..` or `..3`
+    PatternRange {
+        lhs: Option<NodeId>,
+        rhs: Option<NodeId>,
+    },
+    /// List pattern, e.g., `[$head, ..$rest]`, with item patterns stored as a list
+    PatternList(ListId),
+    /// Rest of a list pattern (`..` or `..$rest`) with an optional binding
+    PatternRest(Option<NodeId>),
+    /// Record pattern, e.g., `{name: $n}`, with field patterns stored as a record
+    PatternRecord(RecordId),
+    /// Alternative patterns, e.g., `1 | 2`, stored as a list
+    PatternOr(ListId),
+    /// Pattern with an `if` guard
+    PatternGuard {
+        pattern: NodeId,
+        guard: NodeId,
+    },
     Garbage,
 }
 
@@ -374,6 +454,7 @@ impl AstNode {
             AstNode::Pow => 100,
             AstNode::Multiply | AstNode::Divide | AstNode::FloorDiv | AstNode::Modulo => 95,
             AstNode::Plus | AstNode::Minus => 90,
+            AstNode::ShiftLeft | AstNode::ShiftRight => 85,
             AstNode::LessThan
             | AstNode::LessThanOrEqual
             | AstNode::GreaterThan
@@ -383,7 +464,15 @@ impl AstNode {
             | AstNode::RegexMatch
             | AstNode::NotRegexMatch
             | AstNode::In
+            | AstNode::NotIn
+            | AstNode::Has
+            | AstNode::NotHas
+            | AstNode::StartsWith
+            | AstNode::EndsWith
             | AstNode::Append => 80,
+            AstNode::BitAnd => 75,
+            AstNode::BitXor => 70,
+            AstNode::BitOr => 60,
             AstNode::And => 50,
             AstNode::Xor => 45,
             AstNode::Or => 40,
@@ -398,6 +487,29 @@ impl AstNode {
     }
 }
 
+/// Operator spelled as a bareword, e.g., `mod` or `starts-with`
+fn bareword_operator(word: &[u8]) -> Option<AstNode> {
+    let op = match word {
+        b"mod" => AstNode::Modulo,
+        b"in" => AstNode::In,
+        b"not-in" => AstNode::NotIn,
+        b"has" => AstNode::Has,
+        b"not-has" => AstNode::NotHas,
+        b"starts-with" => AstNode::StartsWith,
+        b"ends-with" => AstNode::EndsWith,
+        b"bit-and" => AstNode::BitAnd,
+        b"bit-xor" => AstNode::BitXor,
+        b"bit-or" => AstNode::BitOr,
+        b"bit-shl" => AstNode::ShiftLeft,
+        b"bit-shr" => AstNode::ShiftRight,
+        b"and" => AstNode::And,
+        b"xor" => AstNode::Xor,
+        b"or" => AstNode::Or,
+        _ => return None,
+    };
+    Some(op)
+}
+
 impl Parser {
     pub fn new(compiler: Compiler, tokens: Tokens) -> Self {
         Self { compiler, tokens }
@@ -411,9 +523,11 @@ impl Parser {
         self.compiler.spans[node_id.0].end
     }
 
+    /// Parse the tokens of the last added or replaced file into its root block
     pub fn parse(mut self) -> Compiler {
         let _span = span!();
-        self.block(BlockContext::Bare);
+        let root = self.block(BlockContext::Bare);
+        self.compiler.set_file_root(root);
 
         self.compiler
     }
@@ -470,11 +584,6 @@ impl Parser {
 
     fn math_expression(&mut self, allow_assignment: bool) -> AssignmentOrExpression {
         let _span = span!();
-        let mut expr_stack = Vec::<(NodeId, NodeId)>::new();
-
-        let mut last_prec = 1000000;
-
-        let span_start = self.position();
 
         // Check for special forms
         if self.is_keyword(b"if") {
@@ -483,13 +592,33 @@ impl Parser {
             return AssignmentOrExpression::Expression(self.match_expression());
         } else if self.is_keyword(b"try") {
             return AssignmentOrExpression::Expression(self.try_expression());
+        } else if self.is_env_shorthand() {
+            return AssignmentOrExpression::Expression(self.env_shorthand());
+        } else if self.is_keyword(b"where") {
+            return AssignmentOrExpression::Expression(self.where_expression());
         }
-        // TODO
-        // } else if self.is_keyword(b"where") {
-        // }
 
         // Otherwise assume a math expression
-        let mut leftmost = self.simple_expression(BarewordContext::Call);
+        self.operator_expression(allow_assignment, BarewordContext::Call)
+    }
+
+    /// Parse operands separated by binary operators
+    ///
+    /// In row conditions, barewords are field shorthands, except for right-hand sides of
+    /// comparisons (e.g., `file` in `type == file`), which are strings.
+    fn operator_expression(
+        &mut self,
+        allow_assignment: bool,
+        bareword_context: BarewordContext,
+    ) -> AssignmentOrExpression {
+        let _span = span!();
+        let mut expr_stack = Vec::<(NodeId, NodeId)>::new();
+
+        let mut last_prec = 1000000;
+
+        let span_start = self.position();
+
+        let mut leftmost = self.simple_expression(bareword_context);
 
         if self.is_equals() {
             if !allow_assignment {
@@ -531,8 +660,16 @@ impl Parser {
                     self.error_on_node("assignment found in expression", op);
                 }
 
+                let rhs_context = match (bareword_context, self.compiler.get_node(op)) {
+                    (BarewordContext::RowCondition, AstNode::And | AstNode::Or | AstNode::Xor) => {
+                        BarewordContext::RowCondition
+                    }
+                    (BarewordContext::RowCondition, _) => BarewordContext::String,
+                    (context, _) => context,
+                };
+
                 let rhs = if self.is_simple_expression() {
-                    self.simple_expression(BarewordContext::Call)
+                    self.simple_expression(rhs_context)
                 } else {
                     self.error("incomplete math expression")
                 };
@@ -623,6 +760,15 @@ impl Parser {
                         node_id
                     }
                     BarewordContext::Call => self.call(),
+                    BarewordContext::RowCondition => {
+                        let it = self.create_node(AstNode::ImplicitIt, span.start, span.start);
+                        let field = self.name();
+                        self.create_node(
+                            AstNode::MemberAccess { target: it, field },
+                            span.start,
+                            span.end,
+                        )
+                    }
                 },
             },
             _ => self.error("incomplete expression"),
@@ -681,6 +827,20 @@ impl Parser {
                         self.error("expected field");
                     }
                 }
+            } else if self.is_path_index() {
+                // Index into a list, e.g., `$list.0`, which is lexed as a float
+                let span = self.tokens.peek_span();
+                self.tokens.advance();
+
+                let index = self.create_node(AstNode::Int, span.start + 1, span.end);
+                expr = self.create_node(
+                    AstNode::MemberAccess {
+                        target: expr,
+                        field: index,
+                    },
+                    span_start,
+                    span.end,
+                );
             } else {
                 return expr;
             }
@@ -732,7 +892,12 @@ impl Parser {
         let span_start = self.position();
 
         while self.has_tokens() {
-            if self.is_newline() {
+            if self.is_newline()
+                || self.is_semicolon()
+                || self.is_pipe()
+                || self.is_rcurly()
+                || self.is_rparen()
+            {
                 break;
             }
 
@@ -826,8 +991,20 @@ impl Parser {
         self.skip_newlines();
 
         // Explicit closure case
-        if self.is_pipe() {
-            let params = Some(self.signature_params(ParamsContext::Pipes));
+        if self.is_pipe() || self.is_pipepipe() {
+            let params = Some(if self.is_pipepipe() {
+                // Closure without parameters `{|| ...}`
+                let (_, span) = self.tokens.peek();
+                self.tokens.advance();
+                self.compiler.params.push(Params::new(vec![]));
+                self.create_node(
+                    AstNode::Params(ParamsId(self.compiler.params.len() - 1)),
+                    span.start,
+                    span.end,
+                )
+            } else {
+                self.signature_params(ParamsContext::Pipes)
+            });
             let block = self.block(BlockContext::Closure);
             self.rcurly();
             span_end = self.position();
@@ -914,17 +1091,20 @@ impl Parser {
             Token::AsteriskEquals => self.advance_node(AstNode::MultiplyAssignment, span),
             Token::ForwardSlashEquals => self.advance_node(AstNode::DivideAssignment, span),
             Token::PlusPlusEquals => self.advance_node(AstNode::AppendAssignment, span),
-            Token::Bareword => match self.compiler.get_span_contents_manual(span.start, span.end) {
-                b"mod" => self.advance_node(AstNode::Modulo, span),
-                b"in" => self.advance_node(AstNode::In, span),
-                b"and" => self.advance_node(AstNode::And, span),
-                b"xor" => self.advance_node(AstNode::Xor, span),
-                b"or" => self.advance_node(AstNode::Or, span),
-                op => self.error(format!(
-                    "Unknown operator: '{}'",
-                    String::from_utf8_lossy(op)
-                )),
-            },
+            Token::Bareword => {
+                let span = self.peek_dashed_word();
+                let word = self.compiler.get_span_contents_manual(span.start, span.end);
+                match bareword_operator(word) {
+                    Some(op) => {
+                        self.advance_past(span.end);
+                        self.create_node(op, span.start, span.end)
+                    }
+                    None => self.error(format!(
+                        "Unknown operator: '{}'",
+                        String::from_utf8_lossy(word)
+                    )),
+                }
+            }
             _ => self.error("expected: operator"),
         }
     }
@@ -966,8 +1146,18 @@ impl Parser {
             self.tokens.advance();
             let (next_token, next_span) = self.tokens.peek();
 
-            if next_span.start > span.end {
-                // horizontal whitespace
+            if next_span.start > span.end
+                || [
+                    Token::Eof,
+                    Token::Newline,
+                    Token::Semicolon,
+                    Token::Pipe,
+                    Token::RCurly,
+                    Token::RParen,
+                ]
+                .contains(&next_token)
+            {
+                // horizontal whitespace or end of the call, like in `call()`
                 break;
             }
 
@@ -1003,25 +1193,32 @@ impl Parser {
                 span_end = self.position() + 1;
                 self.rcurly();
                 break;
-            } else if self.is_simple_expression() {
-                let pattern = self.simple_expression(BarewordContext::String);
+            } else if !self.has_tokens() {
+                span_end = self.position();
+                self.error("expected right curly brace '}'");
+                break;
+            } else if self.is_newline() || self.is_comma() {
+                self.tokens.advance();
+            } else if self.is_simple_expression() || self.is_dotdot() {
+                let pattern = self.match_arm_pattern();
 
                 if !self.is_thick_arrow() {
-                    return self.error("expected thick arrow (=>) between match cases");
+                    self.error("expected thick arrow (=>) between match cases");
+                    self.synchronize();
+                    continue;
                 }
                 self.tokens.advance();
 
-                let pattern_result = self.simple_expression(BarewordContext::String);
-
-                if self.is_comma() {
-                    self.tokens.advance();
-                }
+                let pattern_result = if self.is_lcurly() {
+                    self.block(BlockContext::Curlies)
+                } else {
+                    self.expression()
+                };
 
                 match_arms.push((pattern, pattern_result));
-            } else if self.is_newline() {
-                self.tokens.advance();
             } else {
-                return self.error("expected match arm in match");
+                self.error("expected match arm in match");
+                self.synchronize();
             }
         }
 
@@ -1033,6 +1230,288 @@ impl Parser {
         )
     }
 
+    /// Pattern of a match arm, optionally followed by an `if` guard
+    pub fn match_arm_pattern(&mut self) -> NodeId {
+        let _span = span!();
+        let pattern = self.pattern();
+
+        if self.is_keyword(b"if") {
+            self.tokens.advance();
+            let guard = self.expression();
+            let (span_start, span_end) = self.spanning(pattern, guard);
+
+            self.create_node(
+                AstNode::PatternGuard { pattern, guard },
+                span_start,
+                span_end,
+            )
+        } else {
+            pattern
+        }
+    }
+
+    /// Pattern with optional alternatives separated by '|'
+    pub fn pattern(&mut self) -> NodeId {
+        let _span = span!();
+        let span_start = self.position();
+        let first = self.simple_pattern();
+
+        if !self.is_pipe() {
+            return first;
+        }
+
+        let mut alternatives = vec![first];
+        while self.is_pipe() {
+            self.pipe();
+            alternatives.push(self.simple_pattern());
+        }
+
+        let span_end = self.get_span_end(*alternatives.last().expect("missing alternative"));
+        self.compiler.lists.push(List::new(alternatives));
+        self.create_node(
+            AstNode::PatternOr(ListId(self.compiler.lists.len() - 1)),
+            span_start,
+            span_end,
+        )
+    }
+
+    pub fn simple_pattern(&mut self) -> NodeId {
+        let _span = span!();
+        let (token, span) = self.tokens.peek();
+
+        match token {
+            Token::Dollar => self.pattern_binding(),
+            Token::LSquare => self.list_pattern(),
+            Token::LCurly => self.record_pattern(),
+            Token::Bareword
+                if self.compiler.get_span_contents_manual(span.start, span.end) == b"_" =>
+            {
+                self.advance_node(AstNode::PatternWildcard, span)
+            }
+            Token::DotDot => {
+                // Range without a start, e.g., `..3`
+                self.tokens.advance();
+                let rhs = self.simple_expression(BarewordContext::String);
+                let span_end = self.get_span_end(rhs);
+
+                self.create_node(
+                    AstNode::PatternRange {
+                        lhs: None,
+                        rhs: Some(rhs),
+                    },
+                    span.start,
+                    span_end,
+                )
+            }
+            Token::Int | Token::Float if self.is_range_from() => {
+                // Range without an end, e.g., `4..`
+                let lhs = if token == Token::Int {
+                    self.advance_node(AstNode::Int, span)
+                } else {
+                    self.advance_node(AstNode::Float, span)
+                };
+                let span_end = self.tokens.peek_span().end;
+                self.tokens.advance();
+
+                self.create_node(
+                    AstNode::PatternRange {
+                        lhs: Some(lhs),
+                        rhs: None,
+                    },
+                    span.start,
+                    span_end,
+                )
+            }
+            _ if self.is_simple_expression() => {
+                let value = self.simple_expression(BarewordContext::String);
+                let value_span = self.compiler.get_span(value);
+
+                if let AstNode::Range { lhs, rhs } = *self.compiler.get_node(value) {
+                    // reuse the range node instead of wrapping it
+                    *self.compiler.get_node_mut(value) = AstNode::PatternRange {
+                        lhs: Some(lhs),
+                        rhs: Some(rhs),
+                    };
+                    value
+                } else {
+                    self.create_node(
+                        AstNode::PatternValue(value),
+                        value_span.start,
+                        value_span.end,
+                    )
+                }
+            }
+            _ => self.error("expected pattern"),
+        }
+    }
+
+    pub fn pattern_binding(&mut self) -> NodeId {
+        let node_id = self.variable();
+
+        if let AstNode::Variable = self.compiler.get_node(node_id) {
+            *self.compiler.get_node_mut(node_id) = AstNode::PatternBinding;
+        }
+
+        node_id
+    }
+
+    pub fn list_pattern(&mut self) -> NodeId {
+        let _span = span!();
+        let span_start = self.position();
+        let mut items = vec![];
+
+        self.lsquare();
+        let span_end;
+
+        loop {
+            if self.is_rsquare() {
+                span_end = self.position() + 1;
+                self.tokens.advance();
+                break;
+            } else if self.is_comma() || self.is_newline() {
+                self.tokens.advance();
+            } else if self.is_dotdot() {
+                let rest_span_start = self.position();
+                let mut rest_span_end = rest_span_start + 2;
+                self.tokens.advance();
+
+                let binding = if self.is_dollar() && !self.is_horizontal_space() {
+                    let binding = self.pattern_binding();
+                    rest_span_end = self.get_span_end(binding);
+                    Some(binding)
+                } else {
+                    None
+                };
+
+                items.push(self.create_node(
+                    AstNode::PatternRest(binding),
+                    rest_span_start,
+                    rest_span_end,
+                ));
+            } else if self.is_eof() {
+                span_end = self.position();
+                self.error("unclosed list pattern");
+                break;
+            } else {
+                items.push(self.pattern());
+            }
+        }
+
+        self.compiler.lists.push(List::new(items));
+        self.create_node(
+            AstNode::PatternList(ListId(self.compiler.lists.len() - 1)),
+            span_start,
+            span_end,
+        )
+    }
+
+    pub fn record_pattern(&mut self) -> NodeId {
+        let _span = span!();
+        let span_start = self.position();
+        let mut fields = vec![];
+
+        self.lcurly();
+        let span_end;
+
+        loop {
+            if self.is_rcurly() {
+                span_end = self.position() + 1;
+                self.tokens.advance();
+                break;
+            } else if self.is_comma() || self.is_newline() {
+                self.tokens.advance();
+            } else if self.is_eof() {
+                span_end = self.position();
+                self.error("unclosed record pattern");
+                break;
+            } else {
+                let key = if self.is_string() {
+                    self.string()
+                } else {
+                    self.name()
+                };
+                self.colon();
+                self.skip_newlines();
+                let value = self.pattern();
+                fields.push((key, value));
+            }
+        }
+
+        self.compiler.records.push(Record::new(fields));
+        self.create_node(
+            AstNode::PatternRecord(RecordId(self.compiler.records.len() - 1)),
+            span_start,
+            span_end,
+        )
+    }
+
+    pub fn env_shorthand(&mut self) -> NodeId {
+        let _span = span!();
+        let span_start = self.position();
+
+        let name = self.name();
+        self.equals();
+
+        let value = if self.is_horizontal_space() || self.is_newline() || self.is_eof() {
+            self.error("expected environment variable value")
+        } else {
+            let value = self.simple_expression(BarewordContext::String);
+            if let AstNode::Int | AstNode::Float | AstNode::True | AstNode::False | AstNode::Null =
+                self.compiler.get_node(value)
+            {
+                // the value of the shorthand is always a string
+                *self.compiler.get_node_mut(value) = AstNode::String;
+            }
+            value
+        };
+
+        let expr = if self.is_newline() || self.is_semicolon() || self.is_eof() {
+            self.error("expected expression after environment variable shorthand")
+        } else {
+            self.math_expression(false).get_node_id()
+        };
+        let span_end = self.get_span_end(expr);
+
+        self.create_node(
+            AstNode::EnvShorthand { name, value, expr },
+            span_start,
+            span_end,
+        )
+    }
+
+    pub fn where_expression(&mut self) -> NodeId {
+        let _span = span!();
+        let span_start = self.position();
+
+        self.keyword(b"where");
+
+        let condition_start = self.position();
+        let condition = if self.is_simple_expression() || self.is_name() {
+            self.operator_expression(false, BarewordContext::RowCondition)
+                .get_node_id()
+        } else {
+            self.error("expected row condition")
+        };
+        let span_end = self.get_span_end(condition);
+
+        self.compiler.blocks.push(Block::new(vec![condition]));
+        let block = self.create_node(
+            AstNode::Block(BlockId(self.compiler.blocks.len() - 1)),
+            condition_start,
+            span_end,
+        );
+        let closure = self.create_node(
+            AstNode::Closure {
+                params: None,
+                block,
+            },
+            condition_start,
+            span_end,
+        );
+
+        self.create_node(AstNode::Where(closure), span_start, span_end)
+    }
+
     pub fn if_expression(&mut self) -> NodeId {
         let _span = span!();
         let span_start = self.position();
@@ -1155,12 +1634,45 @@ impl Parser {
                     }
                 }
 
-                if self.is_comma() {
+                if self.is_comma() || self.is_newline() {
                     self.tokens.advance();
                     continue;
                 }
 
-                let name = self.name();
+                if self.is_comment() {
+                    // A comment after a parameter on the same line documents it
+                    let span = self.tokens.peek_span();
+                    if let Some(last) = output.last() {
+                        let last_end = self.get_span_end(*last);
+                        if !self.compiler.source[last_end..span.start].contains(&b'\n') {
+                            self.compiler.doc_comments.insert(*last, span);
+                        }
+                    }
+                    self.tokens.advance();
+                    continue;
+                }
+
+                let param_span_start = self.position();
+
+                // record fields cannot be rest parameters or have default values
+                let is_signature = !matches!(params_context, ParamsContext::Angles);
+
+                let rest = if is_signature && self.is_dotdotdot() {
+                    self.tokens.advance();
+                    true
+                } else {
+                    false
+                };
+
+                // a rest parameter missing its name must not swallow the closing delimiter
+                let name = if rest && (self.is_pipe() || self.is_rsquare()) {
+                    let position = self.position();
+                    let name = self.create_node(AstNode::Garbage, position, position);
+                    self.error_on_node("expected: name", name);
+                    name
+                } else {
+                    self.name()
+                };
 
                 let ty = if self.is_colon() {
                     // We have a type
@@ -1171,21 +1683,36 @@ impl Parser {
                     None
                 };
 
-                let name_span = self.compiler.spans[name.0];
-                let param_span_end = if let Some(ty_id) = ty {
-                    self.compiler.spans[ty_id.0].end
+                let default = if is_signature && self.is_equals() {
+                    self.equals();
+                    let default = self.simple_expression(BarewordContext::String);
+                    if rest {
+                        self.error_on_node("rest parameter cannot have a default value", default);
+                    }
+                    Some(default)
                 } else {
-                    name_span.end
+                    None
                 };
 
-                let param =
-                    self.create_node(AstNode::Param { name, ty }, name_span.start, param_span_end);
+                let param_span_end = self.get_span_end(default.or(ty).unwrap_or(name));
+
+                let param = self.create_node(
+                    AstNode::Param {
+                        name,
+                        ty,
+                        default,
+                        rest,
+                    },
+                    param_span_start,
+                    param_span_end,
+                );
 
                 // output.push(self.name());
                 output.push(param);
             }
 
-            span_end = self.position() + 1;
+            // the closing delimiter, or the end of the file if it is missing
+            span_end = self.tokens.peek_span().end;
 
             match params_context {
                 ParamsContext::Pipes => self.pipe(),
@@ -1221,10 +1748,11 @@ impl Parser {
                 continue;
             }
 
-            param_list.push(self.name());
+            param_list.push(self.type_param());
         }
 
-        let span_end = self.position() + 1;
+        // the closing `>`, or the end of the file if it is missing
+        let span_end = self.tokens.peek_span().end;
         self.greater_than();
 
         self.compiler.params.push(Params::new(param_list));
@@ -1235,6 +1763,24 @@ impl Parser {
         )
     }
 
+    /// Type parameter with an optional upper bound, e.g., `T: number`
+    pub fn type_param(&mut self) -> NodeId {
+        let _span = span!();
+        let name = self.name();
+        let bound = if self.is_colon() {
+            self.colon();
+            Some(self.typename())
+        } else {
+            None
+        };
+
+        let (span_start, span_end) = match bound {
+            Some(bound) => self.spanning(name, bound),
+            None => self.spanning(name, name),
+        };
+        self.create_node(AstNode::TypeParam { name, bound }, span_start, span_end)
+    }
+
     pub fn type_args(&mut self) -> NodeId {
         let _span = span!();
         let span_start = self.position();
@@ -1254,7 +1800,20 @@ impl Parser {
                     continue;
                 }
 
-                output.push(self.typename());
+                let ty = self.typename();
+                if self.is_thin_arrow() {
+                    // Input/output type pair, e.g., in `closure<int -> string>`
+                    self.thin_arrow();
+                    let out_ty = self.typename();
+                    let (span_start, span_end) = self.spanning(ty, out_ty);
+                    output.push(self.create_node(
+                        AstNode::InOutType(ty, out_ty),
+                        span_start,
+                        span_end,
+                    ));
+                } else {
+                    output.push(ty);
+                }
             }
 
             span_end = self.position() + 1;
@@ -1274,39 +1833,40 @@ impl Parser {
     pub fn typename(&mut self) -> NodeId {
         let _span = span!();
         if let (Token::Bareword, span) = self.tokens.peek() {
-            let name = self.name();
-            let name_text = self.compiler.get_span_contents(name);
+            let name = self.type_name();
+            let is_record = self.compiler.get_span_contents(name) == b"record";
+            let is_table = self.compiler.get_span_contents(name) == b"table";
 
-            if name_text == b"record" {
+            if (is_record || is_table) && self.is_less_than() {
                 let fields = self.signature_params(ParamsContext::Angles);
-                let optional = if self.is_question_mark() {
-                    // We have an optional type
-                    self.tokens.advance();
-                    true
+                let mut span_end = self.get_span_end(fields);
+                let optional = self.optional_type_marker(&mut span_end);
+                let ast_node = if is_table {
+                    AstNode::TableType {
+                        name,
+                        fields,
+                        optional,
+                    }
                 } else {
-                    false
+                    AstNode::RecordType {
+                        name,
+                        fields,
+                        optional,
+                    }
                 };
-                let span_end = self.position();
-                return self.create_node(
-                    AstNode::RecordType { fields, optional },
-                    span.start,
-                    span_end,
-                );
+                return self.create_node(ast_node, span.start, span_end);
             }
 
+            let mut span_end = self.get_span_end(name);
             let mut args = None;
             if self.is_less_than() {
                 // We have generics
-                args = Some(self.type_args());
+                let args_id = self.type_args();
+                span_end = self.get_span_end(args_id);
+                args = Some(args_id);
             }
 
-            let optional = if self.is_question_mark() {
-                // We have an optional type
-                self.tokens.advance();
-                true
-            } else {
-                false
-            };
+            let optional = self.optional_type_marker(&mut span_end);
             self.create_node(
                 AstNode::Type {
                     name,
@@ -1314,13 +1874,65 @@ impl Parser {
                     optional,
                 },
                 span.start,
-                span.end, // FIXME: this uses the end of the name as its end
+                span_end,
             )
         } else {
             self.error("expect name")
         }
     }
 
+    /// Name of a type, which may contain dashes, e.g., `cell-path`
+    fn type_name(&mut self) -> NodeId {
+        let span = self.peek_dashed_word();
+        self.advance_past(span.end);
+        self.create_node(AstNode::Name, span.start, span.end)
+    }
+
+    /// Span of the bareword at the current position joined with any directly following `-word`
+    /// parts, e.g., `cell-path` or `starts-with`. Does not consume any tokens.
+    fn peek_dashed_word(&mut self) -> Span {
+        let pos = self.tokens.pos();
+        let mut span = self.tokens.peek_span();
+        self.tokens.advance();
+
+        loop {
+            let (token, dash_span) = self.tokens.peek();
+            if token != Token::Dash || dash_span.start != span.end {
+                break;
+            }
+
+            self.tokens.advance();
+            match self.tokens.peek() {
+                (Token::Bareword, next_span) if next_span.start == dash_span.end => {
+                    span.end = next_span.end;
+                    self.tokens.advance();
+                }
+                _ => break,
+            }
+        }
+
+        self.tokens.set_pos(pos);
+        span
+    }
+
+    /// Advance past all tokens ending at or before `span_end`
+    fn advance_past(&mut self, span_end: usize) {
+        while self.has_tokens() && self.tokens.peek_span().end <= span_end {
+            self.tokens.advance();
+        }
+    }
+
+    /// Consume the `?` marking an optional type, if present, extending the type's span end
+    fn optional_type_marker(&mut self, span_end: &mut usize) -> bool {
+        if self.is_question_mark() {
+            *span_end = self.tokens.peek_span().end;
+            self.tokens.advance();
+            true
+        } else {
+            false
+        }
+    }
+
     pub fn in_out_type(&mut self) -> NodeId {
         let _span = span!();
         let span_start = self.position();
@@ -1386,7 +1998,7 @@ impl Parser {
         let mut has_wrapped_flag = false;
 
         // maybe `--env` or `--wrapped`
-        while let (Token::DashDash, _) = self.tokens.peek() {
+        while let (Token::DashDash, dashdash_span) = self.tokens.peek() {
             self.tokens.advance();
             match self.tokens.peek() {
                 // let's make sure that the word is `env` or `wrapped`
@@ -1394,20 +2006,27 @@ impl Parser {
                     let flag_name = self.compiler.get_span_contents_manual(span.start, span.end);
                     if flag_name == b"env" {
                         if has_env_flag {
-                            return self.error("duplicated --env flag");
+                            self.error("duplicated --env flag");
+                            continue;
                         }
                         has_env_flag = true;
                     } else if flag_name == b"wrapped" {
                         if has_wrapped_flag {
-                            return self.error("duplicated --wrapped flag");
+                            self.error("duplicated --wrapped flag");
+                            continue;
                         }
                         has_wrapped_flag = true
                     } else {
-                        return self.error("expect --env or --wrapped");
+                        self.error("expect --env or --wrapped");
+                        continue;
                     }
                     self.tokens.advance();
                 }
-                _ => return self.error("incomplete flag name"),
+                _ => {
+                    let node_id =
+                        self.create_node(AstNode::Garbage, dashdash_span.start, dashdash_span.end);
+                    self.error_on_node("incomplete flag name", node_id);
+                }
             }
         }
 
@@ -1450,6 +2069,38 @@ impl Parser {
         )
     }
 
+    pub fn attribute(&mut self) -> NodeId {
+        let _span = span!();
+        let span_start = self.position();
+
+        self.tokens.advance(); // @
+        if self.position() > span_start + 1 || !self.has_tokens() || self.is_newline() {
+            return self.error("expected attribute name");
+        }
+        let name = self.call_name();
+
+        let mut args = vec![];
+        while self.has_tokens() && !self.is_newline() && !self.is_comment() && !self.is_semicolon()
+        {
+            args.push(self.simple_expression(BarewordContext::String));
+        }
+
+        let span_end = match args.last() {
+            Some(arg) => self.get_span_end(*arg),
+            None => self.get_span_end(name),
+        };
+
+        self.compiler.lists.push(List::new(args));
+        self.create_node(
+            AstNode::Attribute {
+                name,
+                args: ListId(self.compiler.lists.len() - 1),
+            },
+            span_start,
+            span_end,
+        )
+    }
+
     pub fn extern_statement(&mut self) -> NodeId {
         let _span = span!();
         let span_start = self.position();
@@ -1561,6 +2212,8 @@ impl Parser {
         let span_start = self.position();
 
         let mut code_body = vec![];
+        // Comment lines directly above a definition document it
+        let mut doc_comment: Option<Span> = None;
         if let BlockContext::Curlies = context {
             self.lcurly();
         }
@@ -1572,11 +2225,60 @@ impl Parser {
             } else if self.is_rcurly() && context == BlockContext::Closure {
                 // not responsible for parsing it, yield back to the closure pass
                 break;
-            } else if self.is_semicolon() || self.is_newline() || self.is_comment() {
+            } else if self.is_comment() {
+                let span = self.tokens.peek_span();
+                doc_comment = match doc_comment {
+                    Some(doc) if self.is_on_next_line(doc.end, span.start) => {
+                        Some(Span::new(doc.start, span.end))
+                    }
+                    _ => Some(span),
+                };
+                self.tokens.advance();
+                continue;
+            } else if self.is_semicolon() || self.is_newline() {
                 self.tokens.advance();
                 continue;
-            } else if self.is_keyword(b"def") {
-                code_body.push(self.def_statement());
+            }
+
+            let num_errors = self.compiler.errors.len();
+
+            if self.is_keyword(b"def") || self.is_keyword(b"extern") || self.is_at() {
+                let position = self.position();
+                let doc_comment = doc_comment
+                    .take()
+                    .filter(|doc| self.is_on_next_line(doc.end, position));
+
+                let mut attributes = vec![];
+                while self.is_at() {
+                    // a malformed attribute is reported as garbage, which is not kept
+                    let attribute = self.attribute();
+                    if let AstNode::Attribute { .. } = self.compiler.get_node(attribute) {
+                        attributes.push(attribute);
+                    }
+                    while self.is_newline() || self.is_comment() {
+                        self.tokens.advance();
+                    }
+                }
+
+                let node_id = if self.is_keyword(b"def") {
+                    self.def_statement()
+                } else if self.is_keyword(b"extern") {
+                    self.extern_statement()
+                } else {
+                    self.error("expected def or extern after attributes")
+                };
+
+                if let AstNode::Def { .. } | AstNode::Extern { .. } =
+                    self.compiler.get_node(node_id)
+                {
+                    if let Some(doc) = doc_comment {
+                        self.compiler.doc_comments.insert(node_id, doc);
+                    }
+                    if !attributes.is_empty() {
+                        self.compiler.attributes.insert(node_id, attributes);
+                    }
+                }
+                code_body.push(node_id);
             } else if self.is_keyword(b"let") {
                 code_body.push(self.let_statement());
             } else if self.is_keyword(b"mut") {
@@ -1595,8 +2297,8 @@ impl Parser {
                 code_body.push(self.break_statement());
             } else if self.is_keyword(b"alias") {
                 code_body.push(self.alias_statement());
-            } else if self.is_keyword(b"extern") {
-                code_body.push(self.extern_statement());
+            } else if self.is_keyword(b"type") {
+                code_body.push(self.type_alias_statement());
             } else {
                 let exp_span_start = self.position();
                 let pipeline = self.pipeline_or_expression_or_assignment();
@@ -1614,6 +2316,10 @@ impl Parser {
                     code_body.push(pipeline);
                 }
             }
+
+            if self.compiler.errors.len() > num_errors {
+                self.synchronize();
+            }
         }
 
         self.compiler.blocks.push(Block::new(code_body));
@@ -1733,10 +2439,32 @@ impl Parser {
         self.create_node(AstNode::Alias { new_name, old_name }, span_start, span_end)
     }
 
-    pub fn is_operator(&mut self) -> bool {
-        let (token, span) = self.tokens.peek();
+    pub fn type_alias_statement(&mut self) -> NodeId {
+        let _span = span!();
+        let span_start = self.position();
+        self.keyword(b"type");
+        let name = self.name();
+        let type_params = if self.is_less_than() {
+            Some(self.type_params())
+        } else {
+            None
+        };
+        self.equals();
+        let ty = self.typename();
+        let span_end = self.get_span_end(ty);
+        self.create_node(
+            AstNode::TypeAlias {
+                name,
+                type_params,
+                ty,
+            },
+            span_start,
+            span_end,
+        )
+    }
 
-        match token {
+    pub fn is_operator(&mut self) -> bool {
+        match self.tokens.peek_token() {
             Token::Plus
             | Token::PlusPlus
             | Token::Dash
@@ -1759,8 +2487,9 @@ impl Parser {
             | Token::ForwardSlashEquals
             | Token::PlusPlusEquals => true,
             Token::Bareword => {
-                let op = self.compiler.get_span_contents_manual(span.start, span.end);
-                op == b"mod" || op == b"in" || op == b"and" || op == b"xor" || op == b"or"
+                let span = self.peek_dashed_word();
+                let word = self.compiler.get_span_contents_manual(span.start, span.end);
+                bareword_operator(word).is_some()
             }
             _ => false,
         }
@@ -1846,6 +2575,29 @@ impl Parser {
         self.tokens.peek_token() == Token::Dot
     }
 
+    /// Whether the next token is a path member made of digits, e.g., `.0` in `$list.0`
+    pub fn is_path_index(&mut self) -> bool {
+        let (token, span) = self.tokens.peek();
+        token == Token::Float
+            && self
+                .compiler
+                .get_span_contents_manual(span.start, span.end)
+                .strip_prefix(b".")
+                .is_some_and(|digits| digits.iter().all(u8::is_ascii_digit))
+    }
+
+    pub fn is_at(&mut self) -> bool {
+        self.tokens.peek_token() == Token::At
+    }
+
+    pub fn is_pipepipe(&mut self) -> bool {
+        self.tokens.peek_token() == Token::PipePipe
+    }
+
+    pub fn is_dotdotdot(&mut self) -> bool {
+        self.tokens.peek_token() == Token::DotDotDot
+    }
+
     pub fn is_dotdot(&mut self) -> bool {
         self.tokens.peek_token() == Token::DotDot
     }
@@ -1875,6 +2627,20 @@ impl Parser {
         }
     }
 
+    /// Check for the `FOO=bar` of an environment variable shorthand `FOO=bar expr`
+    pub fn is_env_shorthand(&mut self) -> bool {
+        if !self.is_name() {
+            return false;
+        }
+
+        let pos = self.tokens.pos();
+        self.tokens.advance();
+        let is_env_shorthand = self.is_equals() && !self.is_horizontal_space();
+        self.tokens.set_pos(pos);
+
+        is_env_shorthand
+    }
+
     pub fn is_name(&mut self) -> bool {
         self.tokens.peek_token() == Token::Bareword
     }
@@ -1883,6 +2649,29 @@ impl Parser {
         self.tokens.peek_token() == Token::Eof
     }
 
+    /// Whether the next tokens are a number directly followed by `..` with no range end, e.g., `4..`
+    pub fn is_range_from(&mut self) -> bool {
+        let pos = self.tokens.pos();
+        let number_end = self.tokens.peek_span().end;
+        self.tokens.advance();
+
+        let (token, span) = self.tokens.peek();
+        let result = if token == Token::DotDot && span.start == number_end {
+            self.tokens.advance();
+            let (next, next_span) = self.tokens.peek();
+            next_span.start != span.end
+                || !matches!(
+                    next,
+                    Token::Int | Token::Float | Token::Dollar | Token::LParen
+                )
+        } else {
+            false
+        };
+
+        self.tokens.set_pos(pos);
+        result
+    }
+
     pub fn is_horizontal_space(&self) -> bool {
         let span_position = self.tokens.peek_span().start;
         let whitespace: &[u8] = b" \t";
@@ -1937,6 +2726,32 @@ impl Parser {
         node_id
     }
 
+    /// Skip the rest of a broken statement, up to the next newline, semicolon or unmatched closing
+    /// delimiter, so that parsing can resume after a parse error
+    pub fn synchronize(&mut self) {
+        if let Some(Token::Newline | Token::Semicolon) = self.tokens.previous_token() {
+            // the error already consumed the end of the statement
+            return;
+        }
+
+        let mut depth = 0;
+        loop {
+            match self.tokens.peek_token() {
+                Token::Eof => break,
+                Token::Newline | Token::Semicolon if depth == 0 => break,
+                Token::LCurly | Token::LSquare | Token::LParen => depth += 1,
+                Token::RCurly | Token::RSquare | Token::RParen => {
+                    if depth == 0 {
+                        break;
+                    }
+                    depth -= 1;
+                }
+                _ => (),
+            }
+            self.tokens.advance();
+        }
+    }
+
     pub fn create_node(&mut self, ast_node: AstNode, span_start: usize, span_end: usize) -> NodeId {
         self.compiler.spans.push(Span {
             start: span_start,
@@ -2049,13 +2864,20 @@ impl Parser {
         }
     }
 
+    /// Check whether the source between two positions consists of whitespace with a single newline
+    fn is_on_next_line(&self, end: usize, start: usize) -> bool {
+        let between = &self.compiler.source[end..start];
+        between.iter().all(|c| c.is_ascii_whitespace())
+            && between.iter().filter(|c| **c == b'\n').count() == 1
+    }
+
     pub fn skip_newlines(&mut self) {
         while self.is_newline() {
             self.tokens.advance();
         }
     }
 
-    fn get_rollback_point(&self) -> RollbackPoint {
+    fn get_rollback_point(&mut self) -> RollbackPoint {
         self.compiler.get_rollback_point(self.tokens.pos())
     }
 
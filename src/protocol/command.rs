@@ -1,15 +1,50 @@
 pub trait Command: CommandClone {
     fn name(&self) -> &str;
+
+    /// Description of the command, taken from its doc comment
+    fn description(&self) -> &str {
+        ""
+    }
+
+    fn examples(&self) -> &[Example] {
+        &[]
+    }
+
+    fn search_terms(&self) -> &[String] {
+        &[]
+    }
+
+    /// Deprecation notice (possibly empty), if the command is deprecated
+    fn deprecation(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Usage example of a command, given by an `@example` attribute
+#[derive(Clone, Debug, PartialEq)]
+pub struct Example {
+    pub description: String,
+    pub code: String,
 }
 
 #[derive(Clone)]
 pub struct Declaration {
     name: String,
+    pub description: String,
+    pub examples: Vec<Example>,
+    pub search_terms: Vec<String>,
+    pub deprecation: Option<String>,
 }
 
 impl Declaration {
     pub fn new(name: String) -> Self {
-        Self { name }
+        Self {
+            name,
+            description: String::new(),
+            examples: vec![],
+            search_terms: vec![],
+            deprecation: None,
+        }
     }
 }
 
@@ -17,6 +52,22 @@ impl Command for Declaration {
     fn name(&self) -> &str {
         &self.name
     }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn examples(&self) -> &[Example] {
+        &self.examples
+    }
+
+    fn search_terms(&self) -> &[String] {
+        &self.search_terms
+    }
+
+    fn deprecation(&self) -> Option<&str> {
+        self.deprecation.as_deref()
+    }
 }
 
 // Cloning implementation taken from Nushell
@@ -1,11 +1,16 @@
-use crate::protocol::{Command, Declaration};
+use crate::protocol::{Command, Declaration, Example};
 use crate::{
     compiler::Compiler,
     errors::{Severity, SourceError},
-    parser::{AstNode, NodeId, PipelineId},
+    parser::{AstNode, NodeId, PipelineId, ASSIGNMENT_PRECEDENCE},
+    visitor::children,
 };
+use serde_json::{json, Map, Value};
 use std::collections::HashMap;
 
+/// Attributes accepted before `def` and `extern`
+pub const KNOWN_ATTRIBUTES: [&[u8]; 3] = [b"example", b"deprecated", b"search-terms"];
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub struct ScopeId(pub usize);
 
@@ -22,7 +27,7 @@ pub enum FrameType {
 #[derive(Debug, Clone)]
 pub struct Frame {
     pub frame_type: FrameType,
-    pub variables: HashMap<Vec<u8>, NodeId>,
+    pub variables: HashMap<Vec<u8>, VarId>,
     pub type_decls: HashMap<Vec<u8>, NodeId>,
     pub decls: HashMap<Vec<u8>, NodeId>,
     /// Node that defined the scope frame (e.g., a block or overlay)
@@ -49,11 +54,31 @@ pub struct Variable {
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub struct VarId(pub usize);
 
+/// The built-in `$env` variable holding the environment
+pub const ENV_VAR_ID: VarId = VarId(0);
+/// The built-in `$in` variable holding the pipeline input
+pub const IN_VAR_ID: VarId = VarId(1);
+/// The built-in `$nu` variable holding Nushell constants
+pub const NU_VAR_ID: VarId = VarId(2);
+/// The built-in `$it` variable holding the current row in row conditions
+pub const IT_VAR_ID: VarId = VarId(3);
+
+/// Built-in variables, in the order of their VarIds. All but `$it` are defined in the root scope
+/// frame, while `$it` is defined only in the row conditions of `where`.
+const BUILTIN_VARIABLES: [(&[u8], VarId); 4] = [
+    (b"env", ENV_VAR_ID),
+    (b"in", IN_VAR_ID),
+    (b"nu", NU_VAR_ID),
+    (b"it", IT_VAR_ID),
+];
+
 #[derive(Debug, Clone)]
 pub enum TypeDecl {
     /// A type parameter. Holds the parameter name node
     Param(NodeId),
-    // In the future, we may have type aliases, user-defined classes, etc.
+    /// A type alias. Holds the `TypeAlias` node
+    Alias(NodeId),
+    // In the future, we may have user-defined classes, etc.
 }
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
@@ -66,6 +91,7 @@ pub struct DeclId(pub usize);
 pub struct NameBindings {
     pub scope: Vec<Frame>,
     pub scope_stack: Vec<ScopeId>,
+    pub file_scopes: Vec<ScopeId>,
     pub variables: Vec<Variable>,
     pub var_resolution: HashMap<NodeId, VarId>,
     pub type_decls: Vec<TypeDecl>,
@@ -81,6 +107,7 @@ impl NameBindings {
         Self {
             scope: vec![],
             scope_stack: vec![],
+            file_scopes: vec![],
             variables: vec![],
             var_resolution: HashMap::new(),
             type_decls: vec![],
@@ -107,6 +134,8 @@ pub struct Resolver<'a> {
     pub scope: Vec<Frame>,
     /// Stack of currently entered scope frames
     pub scope_stack: Vec<ScopeId>,
+    /// Scope frame of the root block of each resolved file, indexed by FileId
+    pub file_scopes: Vec<ScopeId>,
     /// Variables, indexed by VarId
     pub variables: Vec<Variable>,
     /// Mapping of variable's name node -> Variable
@@ -123,22 +152,40 @@ pub struct Resolver<'a> {
     pub decl_resolution: HashMap<NodeId, DeclId>,
     /// Errors encountered during name binding
     pub errors: Vec<SourceError>,
+    /// Stack of currently entered definitions (`def` nodes)
+    def_stack: Vec<NodeId>,
 }
 
 impl<'a> Resolver<'a> {
-    pub fn new(compiler: &'a Compiler) -> Self {
+    /// Create a resolver continuing from the name bindings of the files resolved so far, as taken
+    /// from the compiler with `Compiler::take_name_bindings()`
+    pub fn new(compiler: &'a Compiler, name_bindings: NameBindings) -> Self {
+        let variables = if name_bindings.variables.is_empty() {
+            // only $env is mutable, e.g., `$env.FOO = "bar"`
+            BUILTIN_VARIABLES
+                .iter()
+                .map(|(_, var_id)| Variable {
+                    is_mutable: *var_id == ENV_VAR_ID,
+                })
+                .collect()
+        } else {
+            name_bindings.variables
+        };
+
         Self {
             compiler,
-            scope: vec![],
+            scope: name_bindings.scope,
             scope_stack: vec![],
-            variables: vec![],
-            var_resolution: HashMap::new(),
-            type_decls: vec![],
-            type_resolution: HashMap::new(),
-            decls: vec![],
-            decl_nodes: vec![],
-            decl_resolution: HashMap::new(),
+            file_scopes: name_bindings.file_scopes,
+            variables,
+            var_resolution: name_bindings.var_resolution,
+            type_decls: name_bindings.type_decls,
+            type_resolution: name_bindings.type_resolution,
+            decls: name_bindings.decls,
+            decl_nodes: name_bindings.decl_nodes,
+            decl_resolution: name_bindings.decl_resolution,
             errors: vec![],
+            def_stack: vec![],
         }
     }
 
@@ -146,6 +193,7 @@ impl<'a> Resolver<'a> {
         NameBindings {
             scope: self.scope,
             scope_stack: self.scope_stack,
+            file_scopes: self.file_scopes,
             variables: self.variables,
             var_resolution: self.var_resolution,
             type_decls: self.type_decls,
@@ -217,27 +265,193 @@ impl<'a> Resolver<'a> {
             }
         }
 
+        let documented: Vec<_> = self
+            .decls
+            .iter()
+            .enumerate()
+            .filter(|(_, decl)| {
+                !decl.description().is_empty()
+                    || !decl.examples().is_empty()
+                    || !decl.search_terms().is_empty()
+                    || decl.deprecation().is_some()
+            })
+            .collect();
+
+        if !documented.is_empty() {
+            result.push_str("==== DECL METADATA ====\n");
+            for (i, decl) in documented {
+                result.push_str(&format!("{i}: {}\n", decl.name()));
+                if !decl.description().is_empty() {
+                    result.push_str(&format!("  description: {:?}\n", decl.description()));
+                }
+                for example in decl.examples() {
+                    result.push_str(&format!(
+                        "  example: {:?} {:?}\n",
+                        example.description, example.code
+                    ));
+                }
+                if !decl.search_terms().is_empty() {
+                    result.push_str(&format!("  search terms: {:?}\n", decl.search_terms()));
+                }
+                if let Some(notice) = decl.deprecation() {
+                    result.push_str(&format!("  deprecated: {notice:?}\n"));
+                }
+            }
+        }
+
         if !self.errors.is_empty() {
             result.push_str("==== SCOPE ERRORS ====\n");
             for error in &self.errors {
-                result.push_str(&format!(
-                    "{:?} (NodeId {}): {}\n",
-                    error.severity, error.node_id.0, error.message
-                ));
+                result.push_str(&format!("{}\n", error.display(self.compiler)));
             }
         }
 
         result
     }
 
+    /// Serialize the scope frames, declarations and name resolution of nodes
+    pub fn to_json(&self) -> Value {
+        /// Names defined in a frame, sorted by name
+        fn names<T: Copy>(names: &HashMap<Vec<u8>, T>, id: impl Fn(T) -> usize) -> Value {
+            let mut names: Vec<_> = names
+                .iter()
+                .map(|(name, value)| (String::from_utf8_lossy(name).to_string(), id(*value)))
+                .collect();
+            names.sort();
+            Value::Object(
+                names
+                    .into_iter()
+                    .map(|(k, v)| (k, v.into()))
+                    .collect::<Map<_, _>>(),
+            )
+        }
+
+        /// Resolution of nodes, sorted by NodeId
+        fn resolution<T: Copy>(resolution: &HashMap<NodeId, T>, id: impl Fn(T) -> usize) -> Value {
+            let mut resolution: Vec<_> = resolution
+                .iter()
+                .map(|(node_id, value)| (node_id.0, id(*value)))
+                .collect();
+            resolution.sort();
+            resolution
+                .into_iter()
+                .map(|(node_id, id)| json!({ "node_id": node_id, "id": id }))
+                .collect()
+        }
+
+        let scopes: Vec<_> = self
+            .scope
+            .iter()
+            .enumerate()
+            .map(|(idx, frame)| {
+                json!({
+                    "id": idx,
+                    "frame_type": format!("{:?}", frame.frame_type),
+                    "node_id": frame.node_id.0,
+                    "variables": names(&frame.variables, |id| id.0),
+                    "type_decls": names(&frame.type_decls, |node_id| node_id.0),
+                    "decls": names(&frame.decls, |node_id| node_id.0),
+                })
+            })
+            .collect();
+
+        let variables: Vec<_> = self
+            .variables
+            .iter()
+            .enumerate()
+            .map(|(idx, var)| json!({ "id": idx, "is_mutable": var.is_mutable }))
+            .collect();
+
+        let type_decls: Vec<_> = self
+            .type_decls
+            .iter()
+            .enumerate()
+            .map(|(idx, type_decl)| match type_decl {
+                TypeDecl::Param(name) => json!({ "id": idx, "param": name.0 }),
+                TypeDecl::Alias(alias) => json!({ "id": idx, "alias": alias.0 }),
+            })
+            .collect();
+
+        let decls: Vec<_> = self
+            .decls
+            .iter()
+            .zip(&self.decl_nodes)
+            .enumerate()
+            .map(|(idx, (decl, node_id))| {
+                json!({ "id": idx, "name": decl.name(), "node_id": node_id.0 })
+            })
+            .collect();
+
+        json!({
+            "scopes": scopes,
+            "variables": variables,
+            "var_resolution": resolution(&self.var_resolution, |id| id.0),
+            "type_decls": type_decls,
+            "type_resolution": resolution(&self.type_resolution, |id| id.0),
+            "decls": decls,
+            "decl_resolution": resolution(&self.decl_resolution, |id| id.0),
+            "errors": self
+                .errors
+                .iter()
+                .map(|error| error.to_json(self.compiler))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    /// Resolve the parsed files that have not been resolved yet, in the order they were added
+    ///
+    /// Each file is resolved in its own root scope frame. Commands, externs, aliases and type
+    /// aliases defined at the top level of a file are visible in the files added after it, where
+    /// they can be shadowed, while its variables stay private to the file.
     pub fn resolve(&mut self) {
-        if !self.compiler.ast_nodes.is_empty() {
-            let last = self.compiler.ast_nodes.len() - 1;
-            let last_node_id = NodeId(last);
-            self.resolve_node(last_node_id)
+        for file in &self.compiler.files[self.file_scopes.len()..] {
+            let Some(root) = file.root else {
+                break;
+            };
+
+            self.enter_root_scope(root);
+            self.enter_file_definitions(root);
+
+            self.enter_scope(root);
+            let file_scope = self.exit_scope();
+            self.resolve_block(root, Some(file_scope));
+            self.file_scopes.push(file_scope);
+
+            self.exit_scope();
         }
     }
 
+    /// Enter the root scope frame containing the built-in variables
+    fn enter_root_scope(&mut self, node_id: NodeId) {
+        let mut frame = Frame::new(FrameType::Scope, node_id);
+        for (name, var_id) in BUILTIN_VARIABLES {
+            if var_id != IT_VAR_ID {
+                frame.variables.insert(name.to_vec(), var_id);
+            }
+        }
+
+        self.scope.push(frame);
+        self.scope_stack.push(ScopeId(self.scope.len() - 1));
+    }
+
+    /// Enter an overlay frame with the top-level definitions of the files resolved so far, with
+    /// later files shadowing earlier ones
+    fn enter_file_definitions(&mut self, root: NodeId) {
+        if self.file_scopes.is_empty() {
+            return;
+        }
+
+        let mut frame = Frame::new(FrameType::Overlay, root);
+        for scope_id in &self.file_scopes {
+            let file_frame = &self.scope[scope_id.0];
+            frame.type_decls.extend(file_frame.type_decls.clone());
+            frame.decls.extend(file_frame.decls.clone());
+        }
+
+        self.scope.push(frame);
+        self.scope_stack.push(ScopeId(self.scope.len() - 1));
+    }
+
     pub fn resolve_node(&mut self, node_id: NodeId) {
         // TODO: Move node_id param to the end, same as in typechecker
         match self.compiler.ast_nodes[node_id.0] {
@@ -270,22 +484,41 @@ impl<'a> Resolver<'a> {
             } => {
                 // define the command before the block to enable recursive calls
                 self.define_decl(name, node_id);
+                self.resolve_attributes(node_id);
 
                 // making sure the def parameters and body end up in the same scope frame
                 self.enter_scope(block);
-                if let Some(type_params) = type_params {
-                    let type_params = self.compiler.get_params(type_params);
-                    for type_param_id in &type_params.nodes {
-                        self.define_type_decl(*type_param_id, TypeDecl::Param(*type_param_id));
-                    }
-                }
+                self.define_type_params(type_params);
                 self.resolve_node(params);
                 if let Some(in_out_types) = in_out_types {
                     self.resolve_node(in_out_types);
                 }
                 let def_scope = self.exit_scope();
 
+                self.def_stack.push(node_id);
                 self.resolve_block(block, Some(def_scope));
+                self.def_stack.pop();
+            }
+            AstNode::TypeAlias {
+                name,
+                type_params,
+                ty,
+            } => {
+                // define the alias before its body so that recursive aliases can be reported
+                self.define_type_decl(name, TypeDecl::Alias(node_id));
+
+                self.enter_scope(node_id);
+                self.define_type_params(type_params);
+                self.resolve_node(ty);
+                self.exit_scope();
+            }
+            AstNode::Extern { name, params } => {
+                self.define_decl(name, node_id);
+                self.resolve_attributes(node_id);
+
+                self.enter_scope(params);
+                self.resolve_node(params);
+                self.exit_scope();
             }
             AstNode::Alias {
                 new_name,
@@ -296,9 +529,15 @@ impl<'a> Resolver<'a> {
             AstNode::Params(_) => {
                 let params = self.compiler.get_params(node_id);
                 for param in &params.nodes {
-                    let AstNode::Param { name, ty } = self.compiler.ast_nodes[param.0] else {
+                    let AstNode::Param {
+                        name, ty, default, ..
+                    } = self.compiler.ast_nodes[param.0]
+                    else {
                         panic!("param is not a param");
                     };
+                    if let Some(default) = default {
+                        self.resolve_node(default);
+                    }
                     self.define_variable(name, false);
                     if let Some(ty) = ty {
                         self.resolve_node(ty);
@@ -338,9 +577,37 @@ impl<'a> Resolver<'a> {
             AstNode::Loop { block } => {
                 self.resolve_node(block);
             }
-            AstNode::BinaryOp { lhs, op: _, rhs } => {
+            AstNode::BinaryOp { lhs, op, rhs } => {
                 self.resolve_node(lhs);
                 self.resolve_node(rhs);
+
+                if self.compiler.get_node(op).precedence() == ASSIGNMENT_PRECEDENCE {
+                    self.check_assignment_target(lhs);
+
+                    if self.is_env_path(lhs) {
+                        self.check_env_assignment(lhs);
+                    }
+                }
+            }
+            AstNode::Where(closure) => {
+                // the row condition is the only place where `$it` is defined
+                self.enter_scope(closure);
+                if let Some(frame) = self.scope.last_mut() {
+                    frame.variables.insert(b"it".to_vec(), IT_VAR_ID);
+                }
+                self.resolve_node(closure);
+                self.exit_scope();
+            }
+            AstNode::ImplicitIt => {
+                self.var_resolution.insert(node_id, IT_VAR_ID);
+            }
+            AstNode::EnvShorthand {
+                name: _,
+                value,
+                expr,
+            } => {
+                self.resolve_node(value);
+                self.resolve_node(expr);
             }
             AstNode::Range { lhs, rhs } => {
                 self.resolve_node(lhs);
@@ -382,9 +649,12 @@ impl<'a> Resolver<'a> {
             AstNode::Match(_) => {
                 let match_node = self.compiler.get_match(node_id);
                 self.resolve_node(match_node.target);
-                for (arm_lhs, arm_rhs) in &match_node.match_arms {
-                    self.resolve_node(*arm_lhs);
-                    self.resolve_node(*arm_rhs);
+                for (pattern, result) in &match_node.match_arms {
+                    // pattern bindings are visible only in the guard and the result of the arm
+                    self.enter_scope(*pattern);
+                    self.resolve_pattern(*pattern, false);
+                    self.resolve_node(*result);
+                    self.exit_scope();
                 }
             }
             AstNode::Statement(node) => self.resolve_node(node),
@@ -394,7 +664,7 @@ impl<'a> Resolver<'a> {
                     self.resolve_node(args);
                 }
             }
-            AstNode::RecordType { fields, .. } => {
+            AstNode::RecordType { fields, .. } | AstNode::TableType { fields, .. } => {
                 let fields = self.compiler.get_params(fields);
                 for field in &fields.nodes {
                     if let AstNode::Param { ty: Some(ty), .. } = self.compiler.get_node(*field) {
@@ -417,13 +687,67 @@ impl<'a> Resolver<'a> {
                 self.resolve_node(out_ty);
             }
             AstNode::Pipeline(pipeline_id) => self.resolve_pipeline(pipeline_id),
-            AstNode::Param { .. } => (/* seems unused for now */),
-            AstNode::NamedValue { .. } => (/* seems unused for now */),
-            // All remaining matches do not contain NodeId => there is nothing to resolve
+            // The remaining nodes neither define names nor open scopes
+            _ => {
+                for child in children(self.compiler, node_id) {
+                    self.resolve_node(child);
+                }
+            }
+        }
+    }
+
+    /// Define variables bound by a match pattern and resolve the values and guards it contains
+    ///
+    /// `in_alternative` is set when the pattern is a part of alternatives (`1 | 2`), where
+    /// variable bindings are not allowed.
+    pub fn resolve_pattern(&mut self, node_id: NodeId, in_alternative: bool) {
+        match self.compiler.ast_nodes[node_id.0] {
+            AstNode::PatternBinding => self.define_pattern_binding(node_id, in_alternative),
+            AstNode::PatternRest(Some(binding)) => {
+                self.define_pattern_binding(binding, in_alternative)
+            }
+            AstNode::PatternValue(value) => self.resolve_node(value),
+            AstNode::PatternRange { lhs, rhs } => {
+                for bound in lhs.into_iter().chain(rhs) {
+                    self.resolve_node(bound);
+                }
+            }
+            AstNode::PatternList(list_id) => {
+                for item in &self.compiler.lists[list_id.0].items {
+                    self.resolve_pattern(*item, in_alternative);
+                }
+            }
+            AstNode::PatternRecord(record_id) => {
+                for (_, value) in &self.compiler.records[record_id.0].pairs {
+                    self.resolve_pattern(*value, in_alternative);
+                }
+            }
+            AstNode::PatternOr(list_id) => {
+                for alternative in &self.compiler.lists[list_id.0].items {
+                    self.resolve_pattern(*alternative, true);
+                }
+            }
+            AstNode::PatternGuard { pattern, guard } => {
+                self.resolve_pattern(pattern, in_alternative);
+                self.resolve_node(guard);
+            }
+            // Wildcards, rests without a binding and garbage do not bind anything
             _ => (),
         }
     }
 
+    fn define_pattern_binding(&mut self, node_id: NodeId, in_alternative: bool) {
+        if in_alternative {
+            self.errors.push(SourceError {
+                message: "variable bindings are not allowed in pattern alternatives".to_string(),
+                node_id,
+                severity: Severity::Error,
+            });
+        } else {
+            self.define_variable(node_id, false);
+        }
+    }
+
     pub fn resolve_pipeline(&mut self, pipeline_id: PipelineId) {
         let pipeline = &self.compiler.pipelines[pipeline_id.0];
 
@@ -432,16 +756,69 @@ impl<'a> Resolver<'a> {
         }
     }
 
+    /// Check whether the node is `$env` or a member of it, e.g., `$env.FOO`
+    fn is_env_path(&self, node_id: NodeId) -> bool {
+        match self.compiler.get_node(node_id) {
+            AstNode::MemberAccess { target, .. } => self.is_env_path(*target),
+            AstNode::Variable => self.var_resolution.get(&node_id) == Some(&ENV_VAR_ID),
+            _ => false,
+        }
+    }
+
+    /// Check that the target of an assignment is a mutable variable or a cell path into one, e.g.,
+    /// `$rec.a.b`
+    fn check_assignment_target(&mut self, node_id: NodeId) {
+        let mut root = node_id;
+        while let AstNode::MemberAccess { target, .. } = self.compiler.get_node(root) {
+            root = *target;
+        }
+
+        let message = match self.compiler.get_node(root) {
+            AstNode::Variable => match self.var_resolution.get(&root) {
+                Some(var_id) if !self.variables[var_id.0].is_mutable => format!(
+                    "cannot assign to immutable variable `{}`",
+                    String::from_utf8_lossy(trim_var_name(self.compiler.get_span_contents(root)))
+                ),
+                // unresolved variables are reported already
+                _ => return,
+            },
+            _ => "expected a variable or a cell path as the assignment target".to_string(),
+        };
+
+        self.errors.push(SourceError {
+            message,
+            node_id,
+            severity: Severity::Error,
+        });
+    }
+
+    /// Environment changes inside a `def` are discarded when the command returns, unless the
+    /// command is defined with `def --env`
+    fn check_env_assignment(&mut self, node_id: NodeId) {
+        let Some(def_id) = self.def_stack.last() else {
+            return;
+        };
+
+        if let AstNode::Def {
+            name, env: false, ..
+        } = self.compiler.get_node(*def_id)
+        {
+            self.errors.push(SourceError {
+                message: format!(
+                    "environment change is local to `{}` (use `def --env` to keep it after the call)",
+                    String::from_utf8_lossy(trim_decl_name(self.compiler.get_span_contents(*name)))
+                ),
+                node_id,
+                severity: Severity::Note,
+            });
+        }
+    }
+
     pub fn resolve_variable(&mut self, unbound_node_id: NodeId) {
         let var_name = trim_var_name(self.compiler.get_span_contents(unbound_node_id));
 
-        if let Some(node_id) = self.find_variable(var_name) {
-            let var_id = self
-                .var_resolution
-                .get(&node_id)
-                .expect("internal error: missing resolved variable");
-
-            self.var_resolution.insert(unbound_node_id, *var_id);
+        if let Some(var_id) = self.find_variable(var_name) {
+            self.var_resolution.insert(unbound_node_id, var_id);
         } else {
             self.errors.push(SourceError {
                 message: format!("variable `{}` not found", String::from_utf8_lossy(var_name)),
@@ -455,8 +832,10 @@ impl<'a> Resolver<'a> {
         let type_name = self.compiler.get_span_contents(unbound_node_id);
 
         match type_name {
-            b"any" | b"list" | b"bool" | b"closure" | b"float" | b"int" | b"nothing"
-            | b"number" | b"string" => return,
+            b"any" | b"binary" | b"bool" | b"cell-path" | b"closure" | b"datetime"
+            | b"directory" | b"duration" | b"error" | b"filesize" | b"float" | b"glob" | b"int"
+            | b"list" | b"nothing" | b"number" | b"oneof" | b"path" | b"range" | b"record"
+            | b"string" | b"table" => return,
             _ => {}
         }
 
@@ -561,6 +940,21 @@ impl<'a> Resolver<'a> {
         let var_name = self.compiler.get_span_contents(var_name_id);
         let var_name = trim_var_name(var_name).to_vec();
 
+        if BUILTIN_VARIABLES.iter().any(|(name, _)| *name == var_name) {
+            self.errors.push(SourceError {
+                message: format!(
+                    "`${}` is a built-in variable and cannot be redefined",
+                    String::from_utf8_lossy(&var_name)
+                ),
+                node_id: var_name_id,
+                severity: Severity::Error,
+            });
+        }
+
+        let var = Variable { is_mutable };
+        self.variables.push(var);
+        let var_id = VarId(self.variables.len() - 1);
+
         let current_scope_id = self
             .scope_stack
             .last()
@@ -568,16 +962,37 @@ impl<'a> Resolver<'a> {
 
         self.scope[current_scope_id.0]
             .variables
-            .insert(var_name, var_name_id);
-
-        let var = Variable { is_mutable };
-        self.variables.push(var);
-        let var_id = VarId(self.variables.len() - 1);
+            .insert(var_name, var_id);
 
         // let the definition of a variable also count as its use
         self.var_resolution.insert(var_name_id, var_id);
     }
 
+    /// Define the type parameters of a generic definition in the current scope frame and resolve
+    /// their bounds, which may refer to any of the parameters
+    fn define_type_params(&mut self, type_params: Option<NodeId>) {
+        let Some(type_params) = type_params else {
+            return;
+        };
+
+        let type_params = self.compiler.get_params(type_params).nodes.clone();
+        for type_param_id in &type_params {
+            let AstNode::TypeParam { name, .. } = *self.compiler.get_node(*type_param_id) else {
+                panic!("internal error: type parameter is not a type parameter");
+            };
+            self.define_type_decl(name, TypeDecl::Param(name));
+        }
+
+        for type_param_id in &type_params {
+            if let AstNode::TypeParam {
+                bound: Some(bound), ..
+            } = *self.compiler.get_node(*type_param_id)
+            {
+                self.resolve_node(bound);
+            }
+        }
+    }
+
     pub fn define_type_decl(&mut self, type_name_id: NodeId, type_decl: TypeDecl) {
         let type_name = self.compiler.get_span_contents(type_name_id).to_vec();
 
@@ -601,7 +1016,8 @@ impl<'a> Resolver<'a> {
         // TODO: Deduplicate code with define_variable()
         let decl_name = self.compiler.get_span_contents(decl_name_id);
         let decl_name = trim_decl_name(decl_name).to_vec();
-        let decl = Declaration::new(String::from_utf8_lossy(&decl_name).to_string());
+        let mut decl = Declaration::new(String::from_utf8_lossy(&decl_name).to_string());
+        self.collect_decl_metadata(&mut decl, decl_node_id);
 
         let current_scope_id = self
             .scope_stack
@@ -620,7 +1036,101 @@ impl<'a> Resolver<'a> {
         self.decl_resolution.insert(decl_name_id, decl_id);
     }
 
-    pub fn find_variable(&self, var_name: &[u8]) -> Option<NodeId> {
+    /// Resolve the attributes of a `Def` or `Extern` node
+    ///
+    /// Attributes are resolved in the scope surrounding the definition, after the definition itself
+    /// so that examples can call the command.
+    fn resolve_attributes(&mut self, decl_node_id: NodeId) {
+        for attribute_id in self.compiler.get_attributes(decl_node_id).to_vec() {
+            let AstNode::Attribute { name, args } = *self.compiler.get_node(attribute_id) else {
+                panic!("internal error: expected attribute");
+            };
+
+            let attribute_name = self.compiler.get_span_contents(name);
+            if !KNOWN_ATTRIBUTES.contains(&attribute_name) {
+                self.errors.push(SourceError {
+                    message: format!(
+                        "unknown attribute `@{}`",
+                        String::from_utf8_lossy(attribute_name)
+                    ),
+                    node_id: name,
+                    severity: Severity::Error,
+                });
+                continue;
+            }
+
+            for arg in self.compiler.lists[args.0].items.clone() {
+                self.resolve_node(arg);
+            }
+        }
+    }
+
+    /// Fill in the description and attribute metadata of a declaration
+    ///
+    /// Only literal strings and closures contribute; arguments of the wrong shape are reported by
+    /// the typechecker.
+    fn collect_decl_metadata(&self, decl: &mut Declaration, decl_node_id: NodeId) {
+        if let Some(description) = self.compiler.get_doc_comment(decl_node_id) {
+            decl.description = description;
+        }
+
+        for attribute_id in self.compiler.get_attributes(decl_node_id) {
+            let AstNode::Attribute { name, args } = *self.compiler.get_node(*attribute_id) else {
+                panic!("internal error: expected attribute");
+            };
+            let args = &self.compiler.lists[args.0].items;
+
+            match self.compiler.get_span_contents(name) {
+                b"example" => {
+                    if let [description, code] = args[..] {
+                        if let (Some(description), AstNode::Closure { block, .. }) = (
+                            self.string_literal(description),
+                            self.compiler.get_node(code),
+                        ) {
+                            // The closure's span runs on to the next token, so the code ends at
+                            // the first `}` after its block
+                            let span = self.compiler.get_span(code);
+                            let block_end = self.compiler.get_span(*block).end;
+                            let rcurly = self
+                                .compiler
+                                .get_span_contents_manual(block_end, span.end)
+                                .iter()
+                                .position(|c| *c == b'}')
+                                .map_or(span.end, |pos| block_end + pos);
+                            let code = self
+                                .compiler
+                                .get_span_contents_manual(span.start + 1, rcurly);
+                            decl.examples.push(Example {
+                                description,
+                                code: String::from_utf8_lossy(code).trim().to_string(),
+                            });
+                        }
+                    }
+                }
+                b"deprecated" => {
+                    let notice = args.first().and_then(|arg| self.string_literal(*arg));
+                    decl.deprecation = Some(notice.unwrap_or_default());
+                }
+                b"search-terms" => {
+                    decl.search_terms
+                        .extend(args.iter().filter_map(|arg| self.string_literal(*arg)));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn string_literal(&self, node_id: NodeId) -> Option<String> {
+        match self.compiler.get_node(node_id) {
+            AstNode::String | AstNode::Name => Some(
+                String::from_utf8_lossy(trim_decl_name(self.compiler.get_span_contents(node_id)))
+                    .to_string(),
+            ),
+            _ => None,
+        }
+    }
+
+    pub fn find_variable(&self, var_name: &[u8]) -> Option<VarId> {
         for scope_id in self.scope_stack.iter().rev() {
             if let Some(id) = self.scope[scope_id.0].variables.get(var_name) {
                 return Some(*id);
@@ -1,11 +1,72 @@
+use crate::formatter::Formatter;
 use crate::ir_generator::IrGenerator;
-use crate::lexer::lex;
-use crate::resolver::Resolver;
-use crate::typechecker::Typechecker;
-use crate::{compiler::Compiler, parser::Parser};
+use crate::lexer::{lex, Tokens};
+use crate::parser::{AstNode, NodeId};
+use crate::resolver::{DeclId, Resolver};
+use crate::typechecker::{Typechecker, INT_TYPE, STRING_TYPE};
+use crate::visitor::{walk, Visitor};
+use crate::{
+    compiler::{Compiler, FileId, Location, Span, JSON_FORMAT_VERSION},
+    parser::Parser,
+};
 
+use serde_json::Value;
 use std::path::Path;
 
+/// Add a file to the compiler, then lex and parse it. Returns the tokens of the file with the
+/// compiler, or `None` if the file does not lex, since lexing errors stop the CLI.
+fn parse_file(mut compiler: Compiler, fname: &str, contents: &[u8]) -> Option<(Compiler, Tokens)> {
+    let span_offset = compiler.span_offset();
+    compiler.add_file(fname, contents);
+    let (tokens, err) = lex(contents, span_offset);
+    err.ok()?;
+
+    let compiler = Parser::new(compiler, tokens.clone()).parse();
+    Some((compiler, tokens))
+}
+
+/// A compiler after the resolver and the typechecker ran, with what the CLI outputs of them
+struct Checked {
+    compiler: Compiler,
+    /// Whether there were no errors before typechecking, so that IR can be generated
+    is_well_formed: bool,
+    /// Name bindings as JSON, with the errors found by the resolver
+    names_json: Value,
+    /// Types as JSON, with the errors found by the typechecker
+    types_json: Value,
+}
+
+/// Resolve and typecheck the files parsed since the last call, merging the results
+fn resolve_and_typecheck(mut compiler: Compiler) -> Checked {
+    let name_bindings = compiler.take_name_bindings();
+    let mut resolver = Resolver::new(&compiler, name_bindings);
+    resolver.resolve();
+    let names_json = resolver.to_json();
+    compiler.merge_name_bindings(resolver.to_name_bindings());
+
+    // Typecheck even when there are errors so that every independent error is reported
+    let is_well_formed = !compiler.has_errors();
+
+    let types = compiler.take_types();
+    let mut typechecker = Typechecker::new(&compiler, types);
+    typechecker.typecheck();
+    let types_json = typechecker.to_json();
+    compiler.merge_types(typechecker.to_types());
+
+    Checked {
+        compiler,
+        is_well_formed,
+        names_json,
+        types_json,
+    }
+}
+
+/// Add a file to the compiler, then parse, resolve and typecheck it
+fn compile_file(compiler: Compiler, fname: &str, contents: &[u8]) -> Compiler {
+    let (compiler, _) = parse_file(compiler, fname, contents).expect("test sources should lex");
+    resolve_and_typecheck(compiler).compiler
+}
+
 fn evaluate_example(fname: &Path) -> String {
     let mut compiler = Compiler::new();
     let contents = std::fs::read_to_string(fname).expect("We only run tests found by glob");
@@ -13,8 +74,13 @@ fn evaluate_example(fname: &Path) -> String {
     let replaced = contents.replace("\r\n", "\n");
     let contents = replaced.as_bytes();
 
+    // errors are shown with the name of the file, which does not depend on where the tests are
+    let name = fname
+        .file_name()
+        .expect("tests are files")
+        .to_string_lossy();
     let span_offset = compiler.span_offset();
-    compiler.add_file(&fname.to_string_lossy(), contents);
+    compiler.add_file(&name, contents);
 
     let (tokens, err) = lex(contents, span_offset);
     if let Err(e) = err {
@@ -28,26 +94,28 @@ fn evaluate_example(fname: &Path) -> String {
 
     let mut result = compiler.display_state();
 
-    if !compiler.errors.is_empty() {
-        return result;
-    }
-
-    let mut resolver = Resolver::new(&compiler);
+    let name_bindings = compiler.take_name_bindings();
+    let mut resolver = Resolver::new(&compiler, name_bindings);
     resolver.resolve();
     result.push_str(&resolver.display_state());
 
     compiler.merge_name_bindings(resolver.to_name_bindings());
 
-    if !compiler.errors.is_empty() {
-        return result;
-    }
+    // Typecheck even when there are errors so that every independent error is reported
+    let is_well_formed = !compiler.has_errors();
 
-    let mut typechecker = Typechecker::new(&compiler);
+    let types = compiler.take_types();
+    let mut typechecker = Typechecker::new(&compiler, types);
     typechecker.typecheck();
     result.push_str(&typechecker.display_state());
 
     compiler.merge_types(typechecker.to_types());
 
+    // IR can only be generated from well-formed and resolved code
+    if !is_well_formed {
+        return result;
+    }
+
     let mut ir_generator = IrGenerator::new(&compiler);
     ir_generator.generate();
     result.push_str(&ir_generator.display_state());
@@ -55,6 +123,77 @@ fn evaluate_example(fname: &Path) -> String {
     result
 }
 
+/// Serialize every stage of the compiler as JSON, like the `--json` CLI option
+fn evaluate_json(fname: &Path) -> String {
+    let contents = std::fs::read_to_string(fname).expect("We only run tests found by glob");
+    let contents = contents.replace("\r\n", "\n");
+
+    let (compiler, tokens) = parse_file(Compiler::new(), "test.nu", contents.as_bytes())
+        .expect("test sources should lex");
+    let tokens_json = tokens.to_json(&compiler.source);
+    let checked = resolve_and_typecheck(compiler);
+
+    // IR can only be generated from well-formed and resolved code
+    let ir_json = checked.is_well_formed.then(|| {
+        let mut ir_generator = IrGenerator::new(&checked.compiler);
+        ir_generator.generate();
+        ir_generator.to_json()
+    });
+
+    let output = serde_json::json!({
+        "version": JSON_FORMAT_VERSION,
+        "tokens": tokens_json,
+        "ast": checked.compiler.to_json(),
+        "names": checked.names_json,
+        "types": checked.types_json,
+        "ir": ir_json,
+    });
+    serde_json::to_string_pretty(&output).expect("JSON values are serializable")
+}
+
+fn evaluate_tree(fname: &Path) -> String {
+    let contents = std::fs::read_to_string(fname).expect("We only run tests found by glob");
+    let contents = contents.replace("\r\n", "\n");
+
+    let mut compiler = compile_file(Compiler::new(), "test.nu", contents.as_bytes());
+
+    let root = compiler.files[0]
+        .root
+        .expect("parsed files have a root block");
+    // the types merged into the compiler are moved into a new typechecker to display them
+    let types = compiler.take_types();
+    let typechecker = Typechecker::new(&compiler, types);
+    let mut result = compiler.display_tree(root, Some(&typechecker));
+
+    if !compiler.errors.is_empty() {
+        result.push_str("==== ERRORS ====\n");
+        for error in &compiler.errors {
+            result.push_str(&format!("{}\n", error.display(&compiler)));
+        }
+    }
+    result
+}
+
+/// Format the source with its comments, or return `None` if it does not parse
+fn format_source(contents: &str) -> Option<String> {
+    let (compiler, tokens) = parse_file(Compiler::new(), "test.nu", contents.as_bytes())?;
+    if compiler.has_errors() {
+        return None;
+    }
+
+    let root = compiler.files[0]
+        .root
+        .expect("parsed files have a root block");
+    Some(Formatter::new(&compiler, tokens.comments()).format(root))
+}
+
+fn evaluate_format(fname: &Path) -> String {
+    let contents = std::fs::read_to_string(fname).expect("We only run tests found by glob");
+    let contents = contents.replace("\r\n", "\n");
+
+    format_source(&contents).expect("formatted files must parse")
+}
+
 fn evaluate_lexer(fname: &Path) -> String {
     let contents = std::fs::read_to_string(fname).expect("We only run tests found by glob");
     // normalize newlines
@@ -78,9 +217,488 @@ fn test_node_output() {
     });
 }
 
+#[test]
+fn test_json_output() {
+    insta::glob!("../tests/json", "*.nu", |path| {
+        insta::assert_snapshot!(evaluate_json(path));
+    });
+}
+
+#[test]
+fn test_tree_output() {
+    insta::glob!("../tests/tree", "*.nu", |path| {
+        insta::assert_snapshot!(evaluate_tree(path));
+    });
+}
+
+#[test]
+fn test_format() {
+    insta::glob!("../tests/fmt", "*.nu", |path| {
+        insta::assert_snapshot!(evaluate_format(path));
+    });
+}
+
+/// Collects the kinds of nodes of an AST, and the source of the leaves, without NodeIds and spans
+struct ShapeCollector {
+    shape: Vec<String>,
+}
+
+impl Visitor for ShapeCollector {
+    fn enter(&mut self, compiler: &Compiler, node_id: NodeId) -> bool {
+        let node = format!("{:?}", compiler.get_node(node_id));
+        let kind: String = node.chars().filter(|c| !c.is_ascii_digit()).collect();
+
+        if !kind.contains(['(', '{']) {
+            // a leaf, such as a name, literal or operator
+            let source = String::from_utf8_lossy(compiler.get_span_contents(node_id));
+            self.shape.push(format!("{kind} {source}"));
+        } else {
+            self.shape.push(kind);
+        }
+        true
+    }
+}
+
+/// The shape of the AST and the comments of a source that parses without errors
+fn ast_shape(contents: &str) -> (Vec<String>, Vec<String>) {
+    let (compiler, tokens) = parse_file(Compiler::new(), "test.nu", contents.as_bytes())
+        .expect("sources that format also lex");
+    let comments = tokens
+        .comments()
+        .iter()
+        .map(|span| {
+            let comment = compiler.get_span_contents_manual(span.start, span.end);
+            String::from_utf8_lossy(comment).trim_end().to_string()
+        })
+        .collect();
+
+    let mut collector = ShapeCollector { shape: vec![] };
+    walk(
+        &compiler,
+        compiler.files[0]
+            .root
+            .expect("parsed files have a root block"),
+        &mut collector,
+    );
+    (collector.shape, comments)
+}
+
+#[test]
+fn test_format_round_trip() {
+    insta::glob!("../tests", "**/*.nu", |path| {
+        let contents = std::fs::read_to_string(path).expect("We only run tests found by glob");
+        let contents = contents.replace("\r\n", "\n");
+
+        // Files with errors are not formatted
+        let Some(formatted) = format_source(&contents) else {
+            return;
+        };
+
+        assert_eq!(
+            ast_shape(&contents),
+            ast_shape(&formatted),
+            "formatting {} changed its AST or comments:\n{formatted}",
+            path.display()
+        );
+        assert_eq!(
+            format_source(&formatted).as_deref(),
+            Some(formatted.as_str()),
+            "formatting {} is not idempotent",
+            path.display()
+        );
+    });
+}
+
 #[test]
 fn test_lexer() {
     insta::glob!("../tests/lex", "*.nu", |path| {
         insta::assert_snapshot!(evaluate_lexer(path));
     });
 }
+
+/// Counts the visits of every node
+struct VisitCounter {
+    entered: Vec<usize>,
+    exited: Vec<usize>,
+}
+
+impl Visitor for VisitCounter {
+    fn enter(&mut self, _compiler: &Compiler, node_id: NodeId) -> bool {
+        self.entered[node_id.0] += 1;
+        true
+    }
+
+    fn exit(&mut self, _compiler: &Compiler, node_id: NodeId) {
+        self.exited[node_id.0] += 1;
+    }
+}
+
+#[test]
+fn test_walk_visits_every_node_once() {
+    insta::glob!("../tests", "*.nu", |path| {
+        let contents = std::fs::read_to_string(path).expect("We only run tests found by glob");
+        let contents = contents.replace("\r\n", "\n");
+
+        let (compiler, _) = parse_file(
+            Compiler::new(),
+            &path.to_string_lossy(),
+            contents.as_bytes(),
+        )
+        .expect("test sources should lex");
+
+        let num_nodes = compiler.ast_nodes.len();
+        let mut counter = VisitCounter {
+            entered: vec![0; num_nodes],
+            exited: vec![0; num_nodes],
+        };
+        walk(&compiler, NodeId(num_nodes - 1), &mut counter);
+
+        for idx in 0..num_nodes {
+            assert!(
+                counter.entered[idx] <= 1,
+                "node {idx} visited twice in {path:?}"
+            );
+            assert_eq!(counter.entered[idx], counter.exited[idx]);
+
+            // Parse errors leave nodes outside of the tree
+            if !compiler.has_errors() {
+                assert_eq!(
+                    counter.entered[idx], 1,
+                    "node {idx} not visited in {path:?}"
+                );
+            }
+        }
+    });
+}
+
+/// Parse each source as a separate file of the same compiler
+fn parse_files(files: &[(&str, &str)]) -> Compiler {
+    files
+        .iter()
+        .fold(Compiler::new(), |compiler, (fname, contents)| {
+            let (compiler, _) =
+                parse_file(compiler, fname, contents.as_bytes()).expect("test sources should lex");
+            compiler
+        })
+}
+
+/// Name of the command defined by a `Def` node
+fn def_name(compiler: &Compiler, node_id: NodeId) -> &[u8] {
+    let AstNode::Def { name, .. } = compiler.ast_nodes[node_id.0] else {
+        panic!("expected def");
+    };
+    compiler.get_span_contents(name)
+}
+
+#[test]
+fn test_node_queries_across_files() {
+    let a = "def foo [] { 1 + 2 }\n";
+    let b = "let x = 1\ndef bar [y: int] { $y * 3 }\n";
+    let compiler = parse_files(&[("a.nu", a), ("b.nu", b)]);
+
+    let two = compiler.node_at_offset(a.find('2').unwrap()).unwrap();
+    assert_eq!(compiler.get_span_contents(two), b"2");
+    assert!(matches!(
+        compiler.get_node(compiler.get_parent(two).unwrap()),
+        AstNode::BinaryOp { .. }
+    ));
+    assert_eq!(
+        def_name(&compiler, compiler.enclosing_def(two).unwrap()),
+        b"foo"
+    );
+
+    let y = compiler
+        .node_at_offset(a.len() + b.find("$y").unwrap())
+        .unwrap();
+    assert_eq!(compiler.get_node(y), &AstNode::Variable);
+    let bar = compiler.enclosing_def(y).unwrap();
+    assert_eq!(def_name(&compiler, bar), b"bar");
+
+    // the body of `bar` is the innermost block, and the root block of b.nu the outermost one
+    let body = compiler.enclosing_block(y).unwrap();
+    assert_eq!(compiler.get_parent(body), Some(bar));
+    let ancestors = compiler.ancestors(y);
+    assert_eq!(ancestors.last(), compiler.files[1].root.as_ref());
+    assert!(ancestors.contains(&bar));
+
+    // outside of any statement, the root block of the file is the innermost node
+    let between = compiler
+        .node_at_offset(a.len() + b.find('\n').unwrap())
+        .unwrap();
+    assert!(matches!(compiler.get_node(between), AstNode::Block(_)));
+    assert_eq!(compiler.get_parent(between), None);
+    assert_eq!(compiler.enclosing_def(between), None);
+
+    assert_eq!(compiler.node_at_offset(a.len() + b.len()), None);
+}
+
+/// State of the compiler after all stages, to compare incremental and full compilation
+fn compiled_state(compiler: &Compiler) -> String {
+    let decls: Vec<_> = compiler.decls.iter().map(|decl| decl.name()).collect();
+    format!(
+        "{}{}\nnode types: {:?}\nscopes: {}\nvariables: {}\ndecls: {:?} {:?}\n",
+        compiler.display_state(),
+        compiler.to_json(),
+        compiler.node_types,
+        compiler.scope.len(),
+        compiler.variables.len(),
+        decls,
+        compiler.decl_nodes,
+    )
+}
+
+/// Types of the nodes of a tree in the order they are visited
+struct TypeCollector {
+    types: Vec<usize>,
+}
+
+impl Visitor for TypeCollector {
+    fn enter(&mut self, compiler: &Compiler, node_id: NodeId) -> bool {
+        self.types.push(compiler.node_types[node_id.0].0);
+        true
+    }
+}
+
+/// State of the files after all stages that does not depend on NodeIds, to compare files of which
+/// some were replaced with files compiled from scratch
+fn files_state(compiler: &Compiler) -> String {
+    let mut result = String::new();
+    for file in &compiler.files {
+        let root = file.root.expect("files should be parsed");
+        let mut collector = TypeCollector { types: vec![] };
+        walk(compiler, root, &mut collector);
+        result.push_str(&format!(
+            "{}\n{}node types: {:?}\n",
+            file.name,
+            compiler.display_tree(root, None),
+            collector.types
+        ));
+    }
+
+    let errors: Vec<_> = compiler
+        .errors
+        .iter()
+        .map(|error| error.display(compiler))
+        .collect();
+    let decls: Vec<_> = compiler.decls.iter().map(|decl| decl.name()).collect();
+    format!(
+        "{result}errors: {errors:#?}\nscopes: {}\nvariables: {}\ntypes: {}\ndecls: {decls:?}\n",
+        compiler.scope.len(),
+        compiler.variables.len(),
+        compiler.types.len(),
+    )
+}
+
+/// Replace a file, then parse it, resolve and typecheck it and the files after it
+fn recompile_file(mut compiler: Compiler, fname: &str, contents: &[u8]) -> Compiler {
+    let file_id = compiler.replace_file(fname, contents);
+    let (tokens, err) = lex(contents, compiler.files[file_id.0].start);
+    err.expect("test sources should lex");
+
+    resolve_and_typecheck(Parser::new(compiler, tokens).parse()).compiler
+}
+
+#[test]
+fn test_replace_file() {
+    let a = "def foo [] { 1 + 2 }\n";
+    let b = "let x = 1\n";
+    let new_a = "# documented\ndef foo [] { 'a' }\nfoo\n";
+    let new_b = "def bar [y: int] { $y * 3 }\nbar $x\n";
+
+    let compile_files = |files: &[(&str, &str)]| {
+        files
+            .iter()
+            .fold(Compiler::new(), |compiler, (fname, contents)| {
+                compile_file(compiler, fname, contents.as_bytes())
+            })
+    };
+
+    // replacing the last file removes its old nodes
+    let compiler = compile_files(&[("a.nu", a), ("b.nu", b)]);
+    let compiler = recompile_file(compiler, "b.nu", new_b.as_bytes());
+    let expected = compile_files(&[("a.nu", a), ("b.nu", new_b)]);
+    assert_eq!(compiled_state(&compiler), compiled_state(&expected));
+
+    // the files after the replaced one are not parsed again, but use its new declarations
+    let b_root = compiler.files[1].root;
+    let compiler = recompile_file(compiler, "a.nu", new_a.as_bytes());
+    assert_eq!(compiler.files[1].root, b_root);
+    assert!(compiler.files[0].root.unwrap().0 > b_root.unwrap().0);
+    let expected = compile_files(&[("a.nu", new_a), ("b.nu", new_b)]);
+    assert_eq!(files_state(&compiler), files_state(&expected));
+
+    // old nodes of the replaced file do not belong to any file
+    let old_foo = compiler.node_at_offset(a.find("foo").unwrap());
+    assert_eq!(old_foo, None);
+    assert_eq!(compiler.file_of_node(NodeId(0)), None);
+    let foo = compiler
+        .node_at_offset(compiler.files[0].start + new_a.find("foo").unwrap())
+        .unwrap();
+    assert_eq!(compiler.file_of_node(foo), Some(FileId(0)));
+    assert_eq!(
+        compiler.file_of_offset(compiler.files[0].start),
+        Some(FileId(0))
+    );
+
+    // parse errors of the files after the replaced one are kept, the other errors are updated
+    let broken_b = "bar $x (\n";
+    let compiler = recompile_file(compiler, "b.nu", broken_b.as_bytes());
+    let compiler = recompile_file(compiler, "a.nu", a.as_bytes());
+    let expected = compile_files(&[("a.nu", a), ("b.nu", broken_b)]);
+    assert_eq!(files_state(&compiler), files_state(&expected));
+
+    // files resolved and typechecked together are updated from where the replaced one was added
+    let compiler = resolve_and_typecheck(parse_files(&[("a.nu", a), ("b.nu", b)])).compiler;
+    let compiler = recompile_file(compiler, "b.nu", new_b.as_bytes());
+    let expected = resolve_and_typecheck(parse_files(&[("a.nu", a), ("b.nu", new_b)])).compiler;
+    assert_eq!(compiled_state(&compiler), compiled_state(&expected));
+
+    // removing a file updates the files after it
+    let mut compiler = recompile_file(compiler, "a.nu", new_a.as_bytes());
+    assert!(!compiler.remove_file("c.nu"));
+    assert!(compiler.remove_file("a.nu"));
+    let compiler = resolve_and_typecheck(compiler).compiler;
+    assert_eq!(
+        files_state(&compiler),
+        files_state(&compile_files(&[("b.nu", new_b)]))
+    );
+
+    let mut compiler = compile_files(&[("a.nu", a)]);
+    compiler.remove_file("a.nu");
+    assert_eq!(compiled_state(&compiler), compiled_state(&Compiler::new()));
+}
+
+/// Declaration called by the call whose name starts at the given offset
+fn call_at(compiler: &Compiler, offset: usize) -> NodeId {
+    (0..compiler.ast_nodes.len())
+        .map(NodeId)
+        .find(|node_id| {
+            matches!(compiler.get_node(*node_id), AstNode::Call(_))
+                && compiler
+                    .get_span(compiler.get_call(*node_id).parts[0])
+                    .start
+                    == offset
+        })
+        .expect("missing call")
+}
+
+fn called_decl(compiler: &Compiler, offset: usize) -> DeclId {
+    compiler.decl_resolution[&call_at(compiler, offset)]
+}
+
+#[test]
+fn test_resolve_across_files() {
+    let a = "def foo []: nothing -> int { 1 }\nlet x = 2\ntype num = int\n";
+    let b = "foo\n$x\nlet y: num = 3\ndef foo [] { 'b' }\nfoo\n";
+    let compiler = [("a.nu", a), ("b.nu", b)]
+        .iter()
+        .fold(Compiler::new(), |compiler, (fname, contents)| {
+            compile_file(compiler, fname, contents.as_bytes())
+        });
+
+    assert_eq!(compiler.file_scopes.len(), 2);
+    assert!(compiler.scope_stack.is_empty());
+
+    // commands and types of earlier files are visible, and can be shadowed
+    let foo_a = compiler.node_at_offset(a.find("foo").unwrap()).unwrap();
+    let decl_a = compiler.decl_resolution[&foo_a];
+    assert_eq!(
+        called_decl(&compiler, a.len() + b.find("foo").unwrap()),
+        decl_a
+    );
+    let decl_b = called_decl(&compiler, a.len() + b.rfind("foo").unwrap());
+    assert_ne!(decl_b, decl_a);
+    assert!(compiler.decl_nodes[decl_b.0].0 > compiler.files[0].root.unwrap().0);
+
+    // calls use the types of the commands of earlier files, which are not typechecked again
+    let call_a = call_at(&compiler, a.len() + b.find("foo").unwrap());
+    assert_eq!(compiler.node_types[call_a.0], INT_TYPE);
+    let call_b = call_at(&compiler, a.len() + b.rfind("foo").unwrap());
+    assert_eq!(compiler.node_types[call_b.0], STRING_TYPE);
+
+    let num = compiler
+        .node_at_offset(a.len() + b.find("num").unwrap())
+        .unwrap();
+    assert!(compiler.type_resolution.contains_key(&num));
+
+    // variables are private to their file, and each error is reported once
+    let errors: Vec<_> = compiler
+        .errors
+        .iter()
+        .map(|error| error.message.as_str())
+        .collect();
+    assert_eq!(errors, ["variable `x` not found"]);
+}
+
+#[test]
+fn test_locations_across_files() {
+    let a = "let x = 1\n";
+    // the missing `)` is reported at the very end of the file, where the next file starts
+    let b = "def foo [] {\n    'ünï' | print $y\n}\n(1";
+    let c = "let z = 2\n";
+    let compiler = [("a.nu", a), ("b.nu", b), ("c.nu", c)]
+        .iter()
+        .fold(Compiler::new(), |compiler, (fname, contents)| {
+            compile_file(compiler, fname, contents.as_bytes())
+        });
+
+    assert_eq!(compiler.file_of_offset(0), Some(FileId(0)));
+    assert_eq!(compiler.file_of_offset(a.len() + 1), Some(FileId(1)));
+    // the end of a file belongs to it only if no other file starts there
+    assert_eq!(compiler.file_of_offset(a.len() + b.len()), Some(FileId(2)));
+    let end = a.len() + b.len() + c.len();
+    assert_eq!(compiler.file_of_offset(end), Some(FileId(2)));
+    assert_eq!(compiler.file_of_offset(end + 1), None);
+
+    let y = a.len() + b.find("$y").unwrap();
+    assert_eq!(
+        compiler.location(y),
+        Some(Location {
+            file: FileId(1),
+            line: 2,
+            column: 19,
+        })
+    );
+    assert_eq!(compiler.display_location(y), "b.nu:2:19");
+
+    let errors: Vec<_> = compiler
+        .errors
+        .iter()
+        .map(|error| error.display(&compiler))
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            "b.nu:4:3: Error: expected: right paren ')'",
+            "b.nu:2:19: Error: variable `y` not found",
+        ]
+    );
+
+    // the JSON of a file has only its own nodes and errors
+    let json = compiler.file_to_json(FileId(1));
+    let ids: Vec<_> = json["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|node| node["id"].as_u64().unwrap() as usize)
+        .collect();
+    assert!(ids
+        .iter()
+        .all(|id| compiler.file_of_node(NodeId(*id)) == Some(FileId(1))));
+    assert_eq!(
+        ids.last().copied(),
+        compiler.files[1].root.map(|root| root.0)
+    );
+    assert_eq!(json["files"][0]["name"], "b.nu");
+    assert_eq!(json["errors"].as_array().unwrap().len(), 2);
+    assert_eq!(
+        compiler.file_to_json(FileId(2))["errors"],
+        Value::Array(vec![])
+    );
+
+    let span = Span::new(a.len(), a.len() + 3);
+    assert_eq!(compiler.file_of(span), Some(FileId(1)));
+    let nu_span: nu_protocol::Span = span.into();
+    assert_eq!((nu_span.start, nu_span.end), (span.start, span.end));
+    assert_eq!(Span::from(nu_span), span);
+}
@@ -4,7 +4,9 @@
 use crate::compiler::Compiler;
 use crate::errors::{Severity, SourceError};
 use crate::parser::{AstNode, NodeId};
-use crate::resolver::{TypeDecl, TypeDeclId};
+use crate::resolver::{TypeDecl, TypeDeclId, VarId, ENV_VAR_ID, IN_VAR_ID, IT_VAR_ID, NU_VAR_ID};
+use crate::visitor::children;
+use serde_json::{json, Value};
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 
@@ -18,7 +20,34 @@ pub struct InOutType {
     pub out_type: TypeId,
 }
 
+/// Signature of a closure type
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClosureType {
+    /// Types of the positional parameters
+    pub params: Vec<TypeId>,
+    /// Number of leading parameters without a default value
+    pub required: usize,
+    /// Type of the arguments collected by the rest parameter, if any
+    pub rest: Option<TypeId>,
+    /// Pipeline input type
+    pub in_type: TypeId,
+    /// Pipeline output type
+    pub out_type: TypeId,
+}
+
+/// Value of a literal in a match pattern, to find the values matched by an earlier arm
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum LiteralValue {
+    Int(i64),
+    /// Bits of the float, so that values can be hashed
+    Float(u64),
+    String(Vec<u8>),
+    Bool(bool),
+    Null,
+}
+
 /// A type variable used for type inference
+#[derive(Clone)]
 pub struct TypeVar {
     lower_bound: TypeId,
     upper_bound: TypeId,
@@ -30,6 +59,9 @@ pub struct TypeVarId(pub usize);
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RecordTypeId(pub usize);
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClosureTypeId(pub usize);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct OneOfId(pub usize);
 
@@ -59,7 +91,15 @@ pub enum Type {
     Bool,
     String,
     Binary,
-    Closure,
+    CellPath,
+    Date,
+    Duration,
+    Filesize,
+    Glob,
+    Range,
+    /// Value of an error (e.g., from `error make`), unlike `Error` which marks a failed typecheck
+    ErrorValue,
+    Closure(ClosureTypeId),
     List(TypeId),
     Stream(TypeId),
     Record(RecordTypeId),
@@ -78,6 +118,15 @@ pub enum Type {
 pub struct Types {
     pub types: Vec<Type>,
     pub node_types: Vec<TypeId>,
+    pub record_types: Vec<Vec<(NodeId, TypeId)>>,
+    pub closure_types: Vec<ClosureType>,
+    pub oneof_types: Vec<HashSet<TypeId>>,
+    pub allof_types: Vec<HashSet<TypeId>>,
+    pub type_vars: Vec<TypeVar>,
+    pub variable_types: Vec<TypeId>,
+    pub decl_types: Vec<Vec<InOutType>>,
+    pub type_param_bounds: HashMap<TypeDeclId, TypeId>,
+    pub recursive_aliases: HashSet<TypeDeclId>,
     pub errors: Vec<SourceError>,
 }
 
@@ -95,6 +144,7 @@ pub const FLOAT_TYPE: TypeId = TypeId(7);
 pub const BOOL_TYPE: TypeId = TypeId(8);
 pub const STRING_TYPE: TypeId = TypeId(9);
 pub const BINARY_TYPE: TypeId = TypeId(10);
+/// Supertype of all closures
 pub const CLOSURE_TYPE: TypeId = TypeId(11);
 
 // Common composite types can be hardcoded as well, like list<any>:
@@ -104,6 +154,19 @@ pub const BYTE_STREAM_TYPE: TypeId = TypeId(13);
 pub const ERROR_TYPE: TypeId = TypeId(14);
 pub const TOP_TYPE: TypeId = TypeId(15);
 pub const BOTTOM_TYPE: TypeId = TypeId(16);
+/// Type of the built-in `$env` variable. Its fields are not known statically, so any field
+/// access is allowed.
+pub const ENV_TYPE: TypeId = TypeId(17);
+
+// Simple types added after the composite ones:
+
+pub const CELL_PATH_TYPE: TypeId = TypeId(18);
+pub const DATE_TYPE: TypeId = TypeId(19);
+pub const DURATION_TYPE: TypeId = TypeId(20);
+pub const FILESIZE_TYPE: TypeId = TypeId(21);
+pub const GLOB_TYPE: TypeId = TypeId(22);
+pub const RANGE_TYPE: TypeId = TypeId(23);
+pub const ERROR_VALUE_TYPE: TypeId = TypeId(24);
 
 pub struct Typechecker<'a> {
     /// Immutable reference to a compiler after the name binding pass
@@ -117,6 +180,8 @@ pub struct Typechecker<'a> {
     /// Record fields used for `RecordType`. Each value in this vector matches with the index in RecordTypeId.
     /// The individual field lists are stored sorted by field name.
     pub record_types: Vec<Vec<(NodeId, TypeId)>>,
+    /// Signatures used for `Closure`. Each value in this vector matches with the index in ClosureTypeId.
+    pub closure_types: Vec<ClosureType>,
     /// Types used for `OneOf`. Each value in this vector matches with the index in OneOfId.
     pub oneof_types: Vec<HashSet<TypeId>>,
     /// Types used for `AllOf`. Each value in this vector matches with the index in AllOfId.
@@ -127,15 +192,57 @@ pub struct Typechecker<'a> {
     pub variable_types: Vec<TypeId>,
     /// Input/output type pairs of each declaration in compiler.decls, indexed by DeclId
     pub decl_types: Vec<Vec<InOutType>>,
+    /// Stack of pipeline input types of the enclosing commands, closures and pipeline elements,
+    /// used as the type of `$in`
+    input_types: Vec<TypeId>,
+    /// Stack of row types of the enclosing row conditions, used as the type of `$it`
+    row_types: Vec<TypeId>,
+    /// Upper bounds of the bounded type parameters
+    type_param_bounds: HashMap<TypeDeclId, TypeId>,
+    /// Types bound to the type parameters of the type aliases being expanded
+    alias_args: HashMap<TypeDeclId, TypeId>,
+    /// Type aliases being expanded, used to detect recursive aliases
+    expanding_aliases: Vec<TypeDeclId>,
+    /// Recursive type aliases that have already been reported
+    recursive_aliases: HashSet<TypeDeclId>,
+    /// Number of nodes of the files typechecked before, which already have their types
+    num_typechecked_nodes: usize,
+    /// Number of types and type variables created for the files typechecked before
+    num_old_types: usize,
+    num_old_type_vars: usize,
     /// Errors encountered during type checking
     pub errors: Vec<SourceError>,
 }
 
 impl<'a> Typechecker<'a> {
-    pub fn new(compiler: &'a Compiler) -> Self {
-        Self {
+    /// Create a typechecker that continues from the types of the files typechecked before, as
+    /// taken from the compiler with `Compiler::take_types()`
+    pub fn new(compiler: &'a Compiler, types: Types) -> Self {
+        let mut typechecker = Self {
             compiler,
-            types: vec![
+            num_typechecked_nodes: types.node_types.len(),
+            num_old_types: types.types.len(),
+            num_old_type_vars: types.type_vars.len(),
+            types: types.types,
+            node_types: types.node_types,
+            record_types: types.record_types,
+            closure_types: types.closure_types,
+            oneof_types: types.oneof_types,
+            allof_types: types.allof_types,
+            type_vars: types.type_vars,
+            variable_types: types.variable_types,
+            decl_types: types.decl_types,
+            input_types: vec![],
+            row_types: vec![],
+            type_param_bounds: types.type_param_bounds,
+            alias_args: HashMap::new(),
+            expanding_aliases: vec![],
+            recursive_aliases: types.recursive_aliases,
+            errors: vec![],
+        };
+
+        if typechecker.types.is_empty() {
+            typechecker.types = vec![
                 // The order must be the same as with the xxx_TYPE constants above
                 Type::Unknown,
                 Type::Forbidden,
@@ -148,34 +255,65 @@ impl<'a> Typechecker<'a> {
                 Type::Bool,
                 Type::String,
                 Type::Binary,
-                Type::Closure,
+                Type::Closure(ClosureTypeId(0)),
                 Type::List(ANY_TYPE),
                 Type::Stream(BINARY_TYPE),
                 Type::Error,
                 Type::Top,
                 Type::Bottom,
-            ],
-            node_types: vec![UNKNOWN_TYPE; compiler.ast_nodes.len()],
-            record_types: Vec::new(),
-            oneof_types: Vec::new(),
-            allof_types: Vec::new(),
-            type_vars: Vec::new(),
-            variable_types: vec![UNKNOWN_TYPE; compiler.variables.len()],
-            decl_types: vec![
-                vec![InOutType {
-                    in_type: ANY_TYPE,
-                    out_type: ANY_TYPE,
-                }];
-                compiler.decls.len()
-            ],
-            errors: vec![],
+                Type::Record(RecordTypeId(0)),
+                Type::CellPath,
+                Type::Date,
+                Type::Duration,
+                Type::Filesize,
+                Type::Glob,
+                Type::Range,
+                Type::ErrorValue,
+            ];
+            // The first record type is the one of $env
+            typechecker.record_types = vec![Vec::new()];
+            // The first closure type is the one of CLOSURE_TYPE. Any closure can be called with
+            // any arguments and input and may return anything.
+            typechecker.closure_types = vec![ClosureType {
+                params: vec![],
+                required: 0,
+                rest: Some(BOTTOM_TYPE),
+                in_type: BOTTOM_TYPE,
+                out_type: TOP_TYPE,
+            }];
         }
+
+        // Nodes, variables and declarations of the new files are not typechecked yet
+        typechecker
+            .node_types
+            .resize(compiler.ast_nodes.len(), UNKNOWN_TYPE);
+        typechecker
+            .variable_types
+            .resize(compiler.variables.len(), UNKNOWN_TYPE);
+        typechecker.decl_types.resize(
+            compiler.decls.len(),
+            vec![InOutType {
+                in_type: ANY_TYPE,
+                out_type: ANY_TYPE,
+            }],
+        );
+
+        typechecker
     }
 
     pub fn to_types(self) -> Types {
         Types {
             types: self.types,
             node_types: self.node_types,
+            record_types: self.record_types,
+            closure_types: self.closure_types,
+            oneof_types: self.oneof_types,
+            allof_types: self.allof_types,
+            type_vars: self.type_vars,
+            variable_types: self.variable_types,
+            decl_types: self.decl_types,
+            type_param_bounds: self.type_param_bounds,
+            recursive_aliases: self.recursive_aliases,
             errors: self.errors,
         }
     }
@@ -201,31 +339,81 @@ impl<'a> Typechecker<'a> {
         if !self.errors.is_empty() {
             result.push_str("==== TYPE ERRORS ====\n");
             for error in &self.errors {
-                result.push_str(&format!(
-                    "{:?} (NodeId {}): {}\n",
-                    error.severity, error.node_id.0, error.message
-                ));
+                result.push_str(&format!("{}\n", error.display(self.compiler)));
             }
         }
 
         result
     }
 
-    /// Typecheck AST nodes, starting from the last node
+    /// Format the type of a node, e.g., for `Compiler::display_tree()`
+    pub fn node_type_to_string(&self, node_id: NodeId) -> String {
+        self.type_to_string(self.node_types[node_id.0])
+    }
+
+    /// Serialize the types of nodes and variables
+    pub fn to_json(&self) -> Value {
+        let node_types: Vec<_> = self
+            .node_types
+            .iter()
+            .map(|ty| self.type_to_string(*ty))
+            .collect();
+        let variable_types: Vec<_> = self
+            .variable_types
+            .iter()
+            .map(|ty| self.type_to_string(*ty))
+            .collect();
+
+        json!({
+            "node_types": node_types,
+            "variable_types": variable_types,
+            "errors": self
+                .errors
+                .iter()
+                .map(|error| error.to_json(self.compiler))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    /// Typecheck the root blocks of the parsed files that were not typechecked yet, in the order
+    /// they were added
+    ///
+    /// Files typechecked before keep the types merged into the compiler, so calls in the new files
+    /// use the types of the commands defined in earlier files without checking them again.
     pub fn typecheck(&mut self) {
-        if !self.compiler.ast_nodes.is_empty() {
-            let last = self.compiler.ast_nodes.len() - 1;
-            let last_node_id = NodeId(last);
-            self.typecheck_node(last_node_id);
+        for (var_id, ty) in [
+            (ENV_VAR_ID, ENV_TYPE),
+            (IN_VAR_ID, ANY_TYPE),
+            (NU_VAR_ID, ANY_TYPE),
+            (IT_VAR_ID, ANY_TYPE),
+        ] {
+            if let Some(var_ty) = self.variable_types.get_mut(var_id.0) {
+                *var_ty = ty;
+            }
+        }
 
-            for i in 0..self.type_vars.len() {
+        // nodes of the files typechecked before already have their types
+        let roots: Vec<_> = self
+            .compiler
+            .files
+            .iter()
+            .map_while(|file| file.root)
+            .filter(|root| root.0 >= self.num_typechecked_nodes)
+            .collect();
+        if !roots.is_empty() {
+            for root in roots {
+                self.typecheck_node(root);
+            }
+
+            // type variables of earlier files have already been eliminated
+            for i in self.num_old_type_vars..self.type_vars.len() {
                 let var = &self.type_vars[i];
                 let bound = var.lower_bound;
                 let cleaned = self.eliminate_type_vars(bound, TypeVarId(0), true);
                 self.types[bound.0] = self.types[cleaned.0];
             }
 
-            for i in 0..self.types.len() {
+            for i in self.num_old_types..self.types.len() {
                 if let Type::Var(var_id) = &self.types[i] {
                     let bound = self.type_vars[var_id.0].lower_bound;
                     self.types[i] = self.types[bound.0];
@@ -255,20 +443,8 @@ impl<'a> Typechecker<'a> {
                 // Params are not supposed to be evaluated
                 self.set_node_type_id(node_id, FORBIDDEN_TYPE);
             }
-            AstNode::Param { name, ty } => {
-                if let Some(ty) = ty {
-                    let ty_id = self.typecheck_type(ty);
-
-                    let var_id = self
-                        .compiler
-                        .var_resolution
-                        .get(&name)
-                        .expect("missing resolved variable");
-                    self.variable_types[var_id.0] = ty_id;
-                    self.set_node_type_id(node_id, ty_id);
-                } else {
-                    self.set_node_type_id(node_id, ANY_TYPE);
-                }
+            AstNode::Param { .. } => {
+                self.typecheck_param(node_id, ANY_TYPE);
             }
             AstNode::TypeArgs(_) => {
                 for arg in &self.compiler.get_type_args(node_id).args {
@@ -280,13 +456,30 @@ impl<'a> Typechecker<'a> {
             AstNode::Block(_) => {
                 self.typecheck_block(node_id, TOP_TYPE);
             }
-            _ => self.error(
-                format!(
-                    "unsupported/unexpected ast node '{:?}' in typechecker",
-                    self.compiler.ast_nodes[node_id.0]
-                ),
-                node_id,
-            ),
+            AstNode::Garbage => self.set_node_type_id(node_id, ERROR_TYPE),
+            _ if self.is_expr(node_id) => {
+                self.typecheck_expr(node_id, TOP_TYPE);
+            }
+            _ => {
+                self.error(
+                    format!(
+                        "unsupported/unexpected ast node '{:?}' in typechecker",
+                        self.compiler.ast_nodes[node_id.0]
+                    ),
+                    node_id,
+                );
+                self.typecheck_children(node_id);
+            }
+        }
+    }
+
+    /// Typecheck the expressions and blocks inside a node that is not supported, so that the
+    /// errors inside it are reported as well
+    fn typecheck_children(&mut self, node_id: NodeId) {
+        for child in children(self.compiler, node_id) {
+            if self.is_expr(child) || matches!(self.compiler.get_node(child), AstNode::Block(_)) {
+                self.typecheck_node(child);
+            }
         }
     }
 
@@ -320,11 +513,33 @@ impl<'a> Typechecker<'a> {
             } => self.typecheck_let(variable_name, ty, initializer, node_id),
             AstNode::Def {
                 name,
+                type_params,
                 params,
                 in_out_types,
                 block,
                 ..
-            } => self.typecheck_def(name, params, in_out_types, block, node_id),
+            } => {
+                self.typecheck_type_params(type_params);
+                self.typecheck_def(name, params, in_out_types, block, node_id);
+                self.typecheck_attributes(node_id);
+            }
+            AstNode::TypeAlias {
+                name,
+                type_params,
+                ty,
+            } => {
+                self.typecheck_type_params(type_params);
+                let type_decl_id = self.compiler.type_resolution[&name];
+                self.expanding_aliases.push(type_decl_id);
+                self.typecheck_type(ty);
+                self.expanding_aliases.pop();
+                self.set_node_type_id(node_id, NONE_TYPE);
+            }
+            AstNode::Extern { params, .. } => {
+                self.typecheck_node(params);
+                self.set_node_type_id(node_id, NONE_TYPE);
+                self.typecheck_attributes(node_id);
+            }
             AstNode::Alias { new_name, old_name } => {
                 self.typecheck_alias(new_name, old_name, node_id)
             }
@@ -344,6 +559,9 @@ impl<'a> Typechecker<'a> {
                 if let Type::List(type_id) = self.type_of(range) {
                     self.variable_types[var_id.0] = type_id;
                     self.set_node_type_id(variable, type_id);
+                } else if let Type::Range = self.type_of(range) {
+                    self.variable_types[var_id.0] = NUMBER_TYPE;
+                    self.set_node_type_id(variable, NUMBER_TYPE);
                 } else {
                     self.variable_types[var_id.0] = ANY_TYPE;
                     self.set_node_type_id(variable, ERROR_TYPE);
@@ -372,16 +590,24 @@ impl<'a> Typechecker<'a> {
                 // TODO make sure we're in a loop
                 self.set_node_type_id(node_id, NONE_TYPE);
             }
+            AstNode::Statement(node) => {
+                // the output of a pipeline ended by `;` is discarded
+                self.typecheck_stmt(node);
+                self.set_node_type_id(node_id, NONE_TYPE);
+            }
             _ if self.is_expr(node_id) => {
                 self.typecheck_expr(node_id, TOP_TYPE);
             }
-            _ => self.error(
-                format!(
-                    "Expected statement to typecheck, got '{:?}'",
-                    self.compiler.ast_nodes[node_id.0]
-                ),
-                node_id,
-            ),
+            _ => {
+                self.error(
+                    format!(
+                        "Expected statement to typecheck, got '{:?}'",
+                        self.compiler.ast_nodes[node_id.0]
+                    ),
+                    node_id,
+                );
+                self.typecheck_children(node_id);
+            }
         }
     }
 
@@ -442,8 +668,15 @@ impl<'a> Typechecker<'a> {
             AstNode::Pipeline(pipeline_id) => {
                 let pipeline = &self.compiler.pipelines[pipeline_id.0];
                 let expressions = pipeline.get_expressions();
-                for inner in expressions {
+                for (i, inner) in expressions.iter().enumerate() {
+                    // the input of each element is the output of the previous one
+                    if i > 0 {
+                        self.input_types.push(self.type_id_of(expressions[i - 1]));
+                    }
                     self.typecheck_expr(*inner, TOP_TYPE);
+                    if i > 0 {
+                        self.input_types.pop();
+                    }
                 }
 
                 // pipeline type is the type of the last expression, since blocks
@@ -452,24 +685,32 @@ impl<'a> Typechecker<'a> {
                     .last()
                     .map_or(NONE_TYPE, |node_id| self.type_id_of(*node_id))
             }
-            AstNode::Closure { params, block } => {
-                // TODO: input/output types
-                if let Some(params_node_id) = params {
-                    self.typecheck_node(params_node_id);
-                }
-
-                self.typecheck_node(block);
-                CLOSURE_TYPE
-            }
+            AstNode::Closure { params, block } => self.typecheck_closure(params, block, expected),
+            // The parser has reported the error
+            AstNode::Garbage => ERROR_TYPE,
             AstNode::BinaryOp { lhs, op, rhs } => self.typecheck_binary_op(lhs, op, rhs),
             AstNode::Variable => {
-                let var_id = self
-                    .compiler
-                    .var_resolution
-                    .get(&node_id)
-                    .expect("missing resolved variable");
-
-                self.variable_types[var_id.0]
+                let Some(var_id) = self.compiler.var_resolution.get(&node_id) else {
+                    // The resolver has reported the unknown variable
+                    self.set_node_type_id(node_id, ERROR_TYPE);
+                    return ERROR_TYPE;
+                };
+
+                if *var_id == IN_VAR_ID {
+                    self.input_types.last().copied().unwrap_or(ANY_TYPE)
+                } else if *var_id == IT_VAR_ID {
+                    self.row_types.last().copied().unwrap_or(ANY_TYPE)
+                } else {
+                    self.variable_types[var_id.0]
+                }
+            }
+            AstNode::ImplicitIt => self.row_types.last().copied().unwrap_or(ANY_TYPE),
+            AstNode::Where(closure) => self.typecheck_where(closure),
+            AstNode::MemberAccess { target, field } => self.typecheck_member_access(target, field),
+            AstNode::EnvShorthand { name, value, expr } => {
+                self.set_node_type_id(name, FORBIDDEN_TYPE);
+                self.typecheck_expr(value, STRING_TYPE);
+                self.typecheck_expr(expr, expected)
             }
             AstNode::If {
                 condition,
@@ -500,11 +741,38 @@ impl<'a> Typechecker<'a> {
                 let parts = self.compiler.get_call(node_id).parts.clone();
                 self.typecheck_call(&parts, node_id)
             }
+            AstNode::Table(_) => self.typecheck_table(node_id),
+            AstNode::Range { lhs, rhs } => {
+                self.typecheck_expr(lhs, NUMBER_TYPE);
+                self.typecheck_expr(rhs, NUMBER_TYPE);
+                RANGE_TYPE
+            }
+            AstNode::Try {
+                try_block,
+                catch_block,
+                finally_block,
+            } => {
+                let try_type = self.typecheck_block(try_block, expected);
+                // without a catch block, nothing is output when the try block fails
+                let catch_type = match catch_block {
+                    Some(catch_block) => self.typecheck_block(catch_block, expected),
+                    None => NOTHING_TYPE,
+                };
+                // the output of the finally block is discarded
+                if let Some(finally_block) = finally_block {
+                    self.typecheck_block(finally_block, TOP_TYPE);
+                }
+                self.create_oneof(HashSet::from([try_type, catch_type]))
+            }
             AstNode::Match(_) => {
                 let match_node = self.compiler.get_match(node_id);
                 // Check all the output types of match
-                let output_types =
-                    self.typecheck_match(&match_node.target, &match_node.match_arms, expected);
+                let output_types = self.typecheck_match(
+                    &match_node.target,
+                    &match_node.match_arms,
+                    expected,
+                    node_id,
+                );
                 if output_types.is_empty() {
                     NOTHING_TYPE
                 } else {
@@ -519,6 +787,7 @@ impl<'a> Typechecker<'a> {
                     ),
                     node_id,
                 );
+                self.typecheck_children(node_id);
                 ERROR_TYPE
             }
         };
@@ -538,6 +807,50 @@ impl<'a> Typechecker<'a> {
         ty_id
     }
 
+    /// Typecheck a table literal as a list of records with the header's columns as fields
+    fn typecheck_table(&mut self, node_id: NodeId) -> TypeId {
+        let table = self.compiler.get_table(node_id);
+        let header = table.header;
+        let rows = table.rows.clone();
+
+        self.typecheck_expr(header, TOP_TYPE);
+        let columns = self.compiler.get_list(header).items.clone();
+
+        let mut column_types = vec![HashSet::new(); columns.len()];
+        for row in rows {
+            self.typecheck_expr(row, TOP_TYPE);
+            let items = &self.compiler.get_list(row).items;
+            if items.len() != columns.len() {
+                self.error(
+                    format!("Expected {} column(s), got {}", columns.len(), items.len()),
+                    row,
+                );
+            }
+            for (types, item) in column_types.iter_mut().zip(items) {
+                types.insert(self.node_types[item.0]);
+            }
+        }
+
+        let mut fields = columns
+            .into_iter()
+            .zip(column_types)
+            .map(|(column, types)| {
+                let ty = if types.is_empty() {
+                    ANY_TYPE
+                } else {
+                    self.create_oneof(types)
+                };
+                (column, ty)
+            })
+            .collect::<Vec<_>>();
+        // Store fields sorted by name
+        fields.sort_by_cached_key(|(name, _)| self.compiler.get_span_contents(*name));
+
+        self.record_types.push(fields);
+        let record_ty = self.push_type(Type::Record(RecordTypeId(self.record_types.len() - 1)));
+        self.push_type(Type::List(record_ty))
+    }
+
     fn is_expr(&mut self, node_id: NodeId) -> bool {
         matches!(
             self.compiler.ast_nodes[node_id.0],
@@ -548,15 +861,21 @@ impl<'a> Typechecker<'a> {
                 | AstNode::False
                 | AstNode::String
                 | AstNode::Variable
+                | AstNode::MemberAccess { .. }
+                | AstNode::EnvShorthand { .. }
+                | AstNode::Where(_)
                 | AstNode::List(_)
                 | AstNode::Record(_)
                 | AstNode::Table(_)
+                | AstNode::Range { .. }
                 | AstNode::Pipeline(_)
                 | AstNode::Closure { .. }
                 | AstNode::BinaryOp { .. }
                 | AstNode::If { .. }
+                | AstNode::Try { .. }
                 | AstNode::Call(_)
                 | AstNode::Match(_)
+                | AstNode::Garbage
         )
     }
 
@@ -565,57 +884,437 @@ impl<'a> Typechecker<'a> {
         target: &NodeId,
         match_arms: &Vec<(NodeId, NodeId)>,
         expected: TypeId,
+        node_id: NodeId,
     ) -> HashSet<TypeId> {
-        self.typecheck_expr(*target, TOP_TYPE);
+        let target_ty = self.typecheck_expr(*target, TOP_TYPE);
 
         let mut output_types = HashSet::new();
-        // typecheck each node
-        let target_id = self.type_id_of(*target);
-        for (match_node, result_node) in match_arms {
-            self.typecheck_node(*match_node);
-            self.typecheck_expr(*result_node, expected);
-
-            let match_id = self.type_id_of(*match_node);
-            match (self.type_of(*target), self.type_of(*match_node)) {
-                // First is of type Any which will always match
-                (Type::Any, _) => {
-                    self.add_resolved_types(&mut output_types, &self.type_id_of(*result_node));
-                }
-                // Same as above but for second
-                (_, Type::Any) => {
-                    self.add_resolved_types(&mut output_types, &self.type_id_of(*result_node));
-                }
-                // the second is one of the possible types of the first
-                (Type::OneOf(id), _) if self.oneof_types[id.0].contains(&match_id) => {
-                    self.add_resolved_types(&mut output_types, &self.type_id_of(*result_node));
-                }
-                // the first is one of the possible types of the second
-                (_, Type::OneOf(id)) if self.oneof_types[id.0].contains(&target_id) => {
-                    self.add_resolved_types(&mut output_types, &self.type_id_of(*result_node));
-                }
-                // the both the target and the one matched against are
-                // oneof<many types> then we need to check if they have any type in common
-                (Type::OneOf(id1), Type::OneOf(id2)) => {
-                    if self.oneof_types[id1.0]
-                        .intersection(&self.oneof_types[id2.0])
-                        .count()
-                        != 0
-                    {
-                        self.add_resolved_types(&mut output_types, &self.type_id_of(*result_node));
-                    } else {
-                        self.error("The target to be matched against and the possible types of the matched arm are completely disjoint", *match_node);
+        for (pattern, result) in match_arms {
+            let can_match = self.typecheck_pattern(*pattern, target_ty);
+            if let AstNode::Block(_) = self.compiler.ast_nodes[result.0] {
+                self.typecheck_block(*result, expected);
+            } else {
+                self.typecheck_expr(*result, expected);
+            }
+
+            if can_match {
+                self.add_resolved_types(&mut output_types, &self.type_id_of(*result));
+            }
+        }
+
+        self.check_match_exhaustiveness(target_ty, match_arms, node_id);
+
+        output_types
+    }
+
+    /// Warn about match arms that can never be reached and about matches that do not cover all
+    /// possible values of the target
+    ///
+    /// Exhaustiveness is checked only for targets of type bool, union types, and literal types
+    /// (int, float, number, string, nothing), where the covered cases can be enumerated.
+    fn check_match_exhaustiveness(
+        &mut self,
+        target_ty: TypeId,
+        match_arms: &[(NodeId, NodeId)],
+        node_id: NodeId,
+    ) {
+        let members = match self.types[target_ty.0] {
+            Type::OneOf(id) => self.oneof_types[id.0].iter().copied().collect::<Vec<_>>(),
+            _ => vec![target_ty],
+        };
+        let check_exhaustive = members.iter().all(|member| match self.types[member.0] {
+            Type::Bool | Type::Int | Type::Float | Type::Number | Type::String | Type::Nothing => {
+                true
+            }
+            Type::List(_) | Type::Record(_) | Type::Binary | Type::Closure(_) => members.len() > 1,
+            _ => false,
+        });
+
+        let mut covered_values = HashSet::new();
+        let mut covered_members = HashSet::new();
+
+        for (pattern, _) in match_arms {
+            let values = self.pattern_values(*pattern);
+            let all_covered = members
+                .iter()
+                .all(|member| covered_members.contains(member));
+            let values_covered = values
+                .as_ref()
+                .is_some_and(|values| values.iter().all(|value| covered_values.contains(value)));
+
+            if all_covered || values_covered {
+                self.warning("unreachable match arm", *pattern);
+                continue;
+            }
+
+            if let AstNode::PatternGuard { .. } = self.compiler.ast_nodes[pattern.0] {
+                // guarded arms do not cover anything
+                continue;
+            }
+
+            if let Some(values) = values {
+                covered_values.extend(values);
+            }
+
+            for member in &members {
+                let bool_covered = self.types[member.0] == Type::Bool
+                    && covered_values.contains(&LiteralValue::Bool(true))
+                    && covered_values.contains(&LiteralValue::Bool(false));
+                if bool_covered || self.pattern_covers(*pattern, *member) {
+                    covered_members.insert(*member);
+                }
+            }
+        }
+
+        if !check_exhaustive {
+            return;
+        }
+
+        let mut not_covered = vec![];
+        for member in &members {
+            if covered_members.contains(member) {
+                continue;
+            }
+            if self.types[member.0] == Type::Bool {
+                for value in [true, false] {
+                    if !covered_values.contains(&LiteralValue::Bool(value)) {
+                        not_covered.push(value.to_string());
                     }
                 }
-                // Check if the two types can be matched
-                (target_id, match_id) if self.is_type_compatible(target_id, match_id) => {
-                    self.add_resolved_types(&mut output_types, &self.type_id_of(*result_node));
+            } else {
+                not_covered.push(self.type_to_string(*member));
+            }
+        }
+
+        if !not_covered.is_empty() {
+            not_covered.sort();
+            self.warning(
+                format!(
+                    "match is not exhaustive (not covered: {})",
+                    not_covered.join(", ")
+                ),
+                node_id,
+            );
+        }
+    }
+
+    /// Get all literal values a pattern consists of
+    ///
+    /// Returns `None` if the pattern does not consist only of literal values. List and record
+    /// patterns are left out: they may contain bindings, rests and nested patterns, so an earlier
+    /// arm covering them cannot be found by comparing values. Arms after a pattern that covers
+    /// the whole type are still found with `pattern_covers()`.
+    fn pattern_values(&self, pattern: NodeId) -> Option<Vec<LiteralValue>> {
+        match self.compiler.ast_nodes[pattern.0] {
+            AstNode::PatternValue(value) => Some(vec![self.literal_value(value)?]),
+            AstNode::PatternOr(list_id) => {
+                let mut values = vec![];
+                for alternative in &self.compiler.lists[list_id.0].items {
+                    values.extend(self.pattern_values(*alternative)?);
+                }
+                Some(values)
+            }
+            _ => None,
+        }
+    }
+
+    /// Parse the value of a literal, so that literals written differently, such as `1` and `0x1`,
+    /// compare equal
+    fn literal_value(&self, node_id: NodeId) -> Option<LiteralValue> {
+        let contents = self.compiler.get_span_contents(node_id);
+        let digits = || -> String {
+            String::from_utf8_lossy(contents)
+                .chars()
+                .filter(|c| *c != '_')
+                .collect()
+        };
+
+        match self.compiler.ast_nodes[node_id.0] {
+            AstNode::Int => {
+                let digits = digits();
+                let (radix, digits) = match digits.get(..2) {
+                    Some("0x") => (16, &digits[2..]),
+                    Some("0o") => (8, &digits[2..]),
+                    Some("0b") => (2, &digits[2..]),
+                    _ => (10, digits.as_str()),
+                };
+                i64::from_str_radix(digits, radix)
+                    .ok()
+                    .map(LiteralValue::Int)
+            }
+            AstNode::Float => {
+                let value = digits().parse::<f64>().ok()?;
+                // `-0.0` equals `0.0`
+                let value = if value == 0.0 { 0.0 } else { value };
+                Some(LiteralValue::Float(value.to_bits()))
+            }
+            AstNode::String => Some(LiteralValue::String(match contents {
+                [b'"', inner @ .., b'"'] => {
+                    let mut unescaped = vec![];
+                    let mut bytes = inner.iter().copied();
+                    while let Some(byte) = bytes.next() {
+                        if byte != b'\\' {
+                            unescaped.push(byte);
+                            continue;
+                        }
+                        unescaped.push(match bytes.next() {
+                            Some(b'b') => b'\x08',
+                            Some(b'f') => b'\x0C',
+                            Some(b'n') => b'\n',
+                            Some(b'r') => b'\r',
+                            Some(b't') => b'\t',
+                            // `\"` and `\\`
+                            Some(escaped) => escaped,
+                            None => break,
+                        });
+                    }
+                    unescaped
+                }
+                [b'\'', inner @ .., b'\''] => inner.to_vec(),
+                bareword => bareword.to_vec(),
+            })),
+            AstNode::True => Some(LiteralValue::Bool(true)),
+            AstNode::False => Some(LiteralValue::Bool(false)),
+            AstNode::Null => Some(LiteralValue::Null),
+            _ => None,
+        }
+    }
+
+    /// Check if a pattern matches every value of the given type
+    fn pattern_covers(&self, pattern: NodeId, ty: TypeId) -> bool {
+        match self.compiler.ast_nodes[pattern.0] {
+            AstNode::PatternWildcard | AstNode::PatternBinding => true,
+            AstNode::PatternValue(value) => {
+                self.types[ty.0] == Type::Nothing
+                    && self.compiler.ast_nodes[value.0] == AstNode::Null
+            }
+            AstNode::PatternList(list_id) => {
+                let items = &self.compiler.lists[list_id.0].items;
+                matches!(self.types[ty.0], Type::List(_))
+                    && items.len() == 1
+                    && matches!(self.compiler.ast_nodes[items[0].0], AstNode::PatternRest(_))
+            }
+            AstNode::PatternRecord(record_id) => {
+                let Type::Record(record_ty_id) = self.types[ty.0] else {
+                    return false;
+                };
+                let fields = &self.record_types[record_ty_id.0];
+                self.compiler.records[record_id.0]
+                    .pairs
+                    .iter()
+                    .all(|(name, value)| {
+                        let name_text = self.compiler.get_span_contents(*name);
+                        fields
+                            .iter()
+                            .find(|(field, _)| self.compiler.get_span_contents(*field) == name_text)
+                            .is_some_and(|(_, field_ty)| self.pattern_covers(*value, *field_ty))
+                    })
+            }
+            AstNode::PatternOr(list_id) => self.compiler.lists[list_id.0]
+                .items
+                .iter()
+                .any(|alternative| self.pattern_covers(*alternative, ty)),
+            _ => false,
+        }
+    }
+
+    /// Typecheck a match pattern against the type of the value being matched
+    ///
+    /// Each pattern node is given the type of the values it matches and the variables it binds are
+    /// typed accordingly. Returns `false` if the pattern can never match a value of `target_ty`.
+    fn typecheck_pattern(&mut self, node_id: NodeId, target_ty: TypeId) -> bool {
+        let (ty_id, can_match) = match self.compiler.ast_nodes[node_id.0] {
+            AstNode::PatternWildcard => (target_ty, true),
+            AstNode::PatternBinding => {
+                self.set_variable_type(node_id, target_ty);
+                (target_ty, true)
+            }
+            AstNode::PatternValue(value) => {
+                let value_ty = self.typecheck_expr(value, TOP_TYPE);
+                (
+                    value_ty,
+                    self.check_pattern_overlap(value_ty, target_ty, node_id),
+                )
+            }
+            AstNode::PatternRange { lhs, rhs } => {
+                let bound_types: Vec<_> = lhs
+                    .into_iter()
+                    .chain(rhs)
+                    .map(|bound| self.typecheck_expr(bound, NUMBER_TYPE))
+                    .collect();
+                let range_ty = match bound_types[..] {
+                    [lhs_ty, rhs_ty] => self.numeric_op_type(lhs_ty, rhs_ty),
+                    [bound_ty] => bound_ty,
+                    _ => ERROR_TYPE,
+                };
+                (
+                    range_ty,
+                    self.check_pattern_overlap(range_ty, target_ty, node_id),
+                )
+            }
+            AstNode::PatternList(list_id) => {
+                if let Some(elem_ty) = self.list_elem_type(target_ty) {
+                    let mut can_match = true;
+                    for item in self.compiler.lists[list_id.0].items.clone() {
+                        if let AstNode::PatternRest(binding) = self.compiler.ast_nodes[item.0] {
+                            let rest_ty = self.push_type(Type::List(elem_ty));
+                            if let Some(binding) = binding {
+                                self.set_variable_type(binding, rest_ty);
+                                self.set_node_type_id(binding, rest_ty);
+                            }
+                            self.set_node_type_id(item, rest_ty);
+                        } else {
+                            can_match &= self.typecheck_pattern(item, elem_ty);
+                        }
+                    }
+                    (self.push_type(Type::List(elem_ty)), can_match)
+                } else {
+                    self.error(
+                        format!(
+                            "list pattern cannot match {}",
+                            self.type_to_string(target_ty)
+                        ),
+                        node_id,
+                    );
+                    (ERROR_TYPE, false)
+                }
+            }
+            AstNode::PatternRecord(record_id) => {
+                let mut can_match = true;
+                let mut fields = vec![];
+                for (name, value) in self.compiler.records[record_id.0].pairs.clone() {
+                    if let Some(field_ty) = self.record_field_type(target_ty, name) {
+                        can_match &= self.typecheck_pattern(value, field_ty);
+                        fields.push((name, self.type_id_of(value)));
+                    } else {
+                        // A value without the field simply does not match the pattern
+                        self.typecheck_pattern(value, ERROR_TYPE);
+                        can_match = false;
+                    }
                 }
-                _ => {
-                    self.error("The types do not match", *match_node);
+                fields.sort_by_cached_key(|(name, _)| self.compiler.get_span_contents(*name));
+
+                self.record_types.push(fields);
+                let record_ty =
+                    self.push_type(Type::Record(RecordTypeId(self.record_types.len() - 1)));
+                (record_ty, can_match)
+            }
+            AstNode::PatternOr(list_id) => {
+                let mut can_match = false;
+                let mut types = HashSet::new();
+                for alternative in self.compiler.lists[list_id.0].items.clone() {
+                    can_match |= self.typecheck_pattern(alternative, target_ty);
+                    types.insert(self.type_id_of(alternative));
                 }
+                (self.create_oneof(types), can_match)
+            }
+            AstNode::PatternGuard { pattern, guard } => {
+                let can_match = self.typecheck_pattern(pattern, target_ty);
+                self.typecheck_expr(guard, BOOL_TYPE);
+                (self.type_id_of(pattern), can_match)
+            }
+            AstNode::Garbage => (ERROR_TYPE, true),
+            _ => {
+                self.error(
+                    format!(
+                        "Expected a pattern to typecheck, got '{:?}'",
+                        self.compiler.ast_nodes[node_id.0]
+                    ),
+                    node_id,
+                );
+                (ERROR_TYPE, false)
+            }
+        };
+        self.set_node_type_id(node_id, ty_id);
+
+        can_match
+    }
+
+    /// Check that a value pattern of type `pattern_ty` can match a value of type `target_ty`
+    fn check_pattern_overlap(
+        &mut self,
+        pattern_ty: TypeId,
+        target_ty: TypeId,
+        node_id: NodeId,
+    ) -> bool {
+        if target_ty == ERROR_TYPE || self.types_overlap(pattern_ty, target_ty) {
+            true
+        } else {
+            self.error("The types do not match", node_id);
+            false
+        }
+    }
+
+    /// Collect the variables that are known not to be null when `condition` evaluates to `outcome`,
+    /// e.g., `$x` in `$x != null` when it is true
+    fn not_null_variables(&self, condition: NodeId, outcome: bool, vars: &mut Vec<VarId>) {
+        let AstNode::BinaryOp { lhs, op, rhs } = self.compiler.ast_nodes[condition.0] else {
+            return;
+        };
+
+        match (&self.compiler.ast_nodes[op.0], outcome) {
+            (AstNode::NotEqual, true) | (AstNode::Equal, false) => {
+                let var = match (
+                    &self.compiler.ast_nodes[lhs.0],
+                    &self.compiler.ast_nodes[rhs.0],
+                ) {
+                    (AstNode::Variable, AstNode::Null) => lhs,
+                    (AstNode::Null, AstNode::Variable) => rhs,
+                    _ => return,
+                };
+                if let Some(var_id) = self.compiler.var_resolution.get(&var) {
+                    vars.push(*var_id);
+                }
+            }
+            (AstNode::And, true) | (AstNode::Or, false) => {
+                self.not_null_variables(lhs, outcome, vars);
+                self.not_null_variables(rhs, outcome, vars);
+            }
+            _ => (),
+        }
+    }
+
+    /// Remove `nothing` from a union type
+    fn exclude_nothing(&mut self, ty: TypeId) -> TypeId {
+        match self.types[ty.0] {
+            Type::OneOf(id) => {
+                let mut members = self.oneof_types[id.0].clone();
+                members.remove(&NOTHING_TYPE);
+                self.create_oneof(members)
+            }
+            _ => ty,
+        }
+    }
+
+    /// Typecheck a membership test of `item` in the list or string `container`
+    fn typecheck_membership(
+        &mut self,
+        item: NodeId,
+        container: NodeId,
+        lhs: NodeId,
+        op: NodeId,
+        rhs: NodeId,
+    ) -> TypeId {
+        let container_type = self.typecheck_expr(container, TOP_TYPE);
+        match self.types[container_type.0] {
+            Type::String => {
+                self.typecheck_expr(item, STRING_TYPE);
+                BOOL_TYPE
+            }
+            Type::List(elem_ty) => {
+                self.typecheck_expr(item, elem_ty);
+                BOOL_TYPE
+            }
+            Type::Any | Type::Bottom => {
+                self.typecheck_expr(item, TOP_TYPE);
+                BOOL_TYPE
+            }
+            _ => {
+                self.typecheck_expr(item, TOP_TYPE);
+                self.binary_op_err("list/string operation", lhs, op, rhs);
+                ERROR_TYPE
             }
         }
-        output_types
     }
 
     fn typecheck_binary_op(&mut self, lhs: NodeId, op: NodeId, rhs: NodeId) -> TypeId {
@@ -660,33 +1359,48 @@ impl<'a> Typechecker<'a> {
                     _ => NUMBER_TYPE,
                 }
             }
-            AstNode::RegexMatch | AstNode::NotRegexMatch => {
+            AstNode::RegexMatch
+            | AstNode::NotRegexMatch
+            | AstNode::StartsWith
+            | AstNode::EndsWith => {
                 self.typecheck_expr(lhs, STRING_TYPE);
                 self.typecheck_expr(rhs, STRING_TYPE);
                 BOOL_TYPE
             }
-            AstNode::In => {
-                let rhs_type = self.typecheck_expr(rhs, TOP_TYPE);
-                match self.types[rhs_type.0] {
-                    Type::String => {
-                        self.typecheck_expr(lhs, STRING_TYPE);
-                        BOOL_TYPE
-                    }
-                    Type::List(elem_ty) => {
-                        self.typecheck_expr(lhs, elem_ty);
-                        BOOL_TYPE
-                    }
-                    Type::Any | Type::Bottom => {
-                        self.typecheck_expr(lhs, TOP_TYPE);
-                        BOOL_TYPE
-                    }
-                    _ => {
-                        self.binary_op_err("list/string operation", lhs, op, rhs);
-                        ERROR_TYPE
-                    }
+            AstNode::In | AstNode::NotIn => self.typecheck_membership(lhs, rhs, lhs, op, rhs),
+            AstNode::Has | AstNode::NotHas => self.typecheck_membership(rhs, lhs, lhs, op, rhs),
+            AstNode::BitAnd
+            | AstNode::BitXor
+            | AstNode::BitOr
+            | AstNode::ShiftLeft
+            | AstNode::ShiftRight => {
+                self.typecheck_expr(lhs, INT_TYPE);
+                self.typecheck_expr(rhs, INT_TYPE);
+                INT_TYPE
+            }
+            AstNode::And | AstNode::Or => {
+                self.typecheck_expr(lhs, BOOL_TYPE);
+
+                // The right-hand side is only evaluated if the left-hand side is true (`and`) or
+                // false (`or`), so the null checks on the left-hand side hold there
+                let is_and = matches!(self.compiler.ast_nodes[op.0], AstNode::And);
+                let mut not_null = vec![];
+                self.not_null_variables(lhs, is_and, &mut not_null);
+
+                let mut narrowed = vec![];
+                for var_id in not_null {
+                    let ty = self.variable_types[var_id.0];
+                    narrowed.push((var_id, ty));
+                    self.variable_types[var_id.0] = self.exclude_nothing(ty);
+                }
+                self.typecheck_expr(rhs, BOOL_TYPE);
+                for (var_id, ty) in narrowed.into_iter().rev() {
+                    self.variable_types[var_id.0] = ty;
                 }
+
+                BOOL_TYPE
             }
-            AstNode::And | AstNode::Xor | AstNode::Or => {
+            AstNode::Xor => {
                 self.typecheck_expr(lhs, BOOL_TYPE);
                 self.typecheck_expr(rhs, BOOL_TYPE);
                 BOOL_TYPE
@@ -750,18 +1464,298 @@ impl<'a> Typechecker<'a> {
                     ERROR_TYPE
                 }
             }
-            AstNode::Assignment
-            | AstNode::AddAssignment
-            | AstNode::SubtractAssignment
-            | AstNode::MultiplyAssignment
-            | AstNode::DivideAssignment
-            | AstNode::AppendAssignment => {
-                // TODO: actually check if operands are right for operator
-                self.typecheck_expr(lhs, TOP_TYPE);
-                self.typecheck_expr(rhs, TOP_TYPE);
-                NONE_TYPE
+            AstNode::Assignment
+            | AstNode::AddAssignment
+            | AstNode::SubtractAssignment
+            | AstNode::MultiplyAssignment
+            | AstNode::DivideAssignment
+            | AstNode::AppendAssignment => {
+                self.typecheck_assignment(lhs, op, rhs);
+                NONE_TYPE
+            }
+            _ => panic!("internal error: unsupported node passed as binary op: {op:?}"),
+        }
+    }
+
+    /// Typecheck an assignment such as `$x = 1` or `$rec.a.b += 2`
+    ///
+    /// The target's mutability is checked by the resolver. Here, the value assigned (the
+    /// right-hand side for `=`, or the result of the operation otherwise) must be compatible with
+    /// the type of the target.
+    fn typecheck_assignment(&mut self, lhs: NodeId, op: NodeId, rhs: NodeId) {
+        let target_ty = self.typecheck_expr(lhs, TOP_TYPE);
+
+        if matches!(self.types[target_ty.0], Type::Any | Type::Error) {
+            self.typecheck_expr(rhs, TOP_TYPE);
+            return;
+        }
+
+        match self.compiler.ast_nodes[op.0] {
+            AstNode::Assignment => {
+                self.typecheck_expr(rhs, target_ty);
+            }
+            AstNode::AddAssignment if self.is_subtype(target_ty, STRING_TYPE) => {
+                self.typecheck_expr(rhs, STRING_TYPE);
+            }
+            AstNode::AddAssignment
+            | AstNode::SubtractAssignment
+            | AstNode::MultiplyAssignment
+            | AstNode::DivideAssignment
+                if self.is_subtype(target_ty, NUMBER_TYPE) =>
+            {
+                let rhs_ty = self.typecheck_expr(rhs, NUMBER_TYPE);
+                let result_ty = self.numeric_op_type(target_ty, rhs_ty);
+                if !self.is_subtype(result_ty, target_ty) {
+                    self.error(
+                        format!(
+                            "Expected {}, got {}",
+                            self.type_to_string(target_ty),
+                            self.type_to_string(result_ty)
+                        ),
+                        rhs,
+                    );
+                }
+            }
+            AstNode::AppendAssignment if self.is_subtype(target_ty, LIST_ANY_TYPE) => {
+                self.typecheck_expr(rhs, target_ty);
+            }
+            _ => {
+                self.typecheck_expr(rhs, TOP_TYPE);
+                self.binary_op_err("assignment", lhs, op, rhs);
+            }
+        }
+    }
+
+    fn typecheck_member_access(&mut self, target: NodeId, field: NodeId) -> TypeId {
+        let target_ty = self.typecheck_expr(target, TOP_TYPE);
+        self.set_node_type_id(field, FORBIDDEN_TYPE);
+
+        if let AstNode::Int = self.compiler.get_node(field) {
+            // Index into a list, e.g., `$list.0`
+            return match self.types[target_ty.0] {
+                Type::List(elem_ty) => elem_ty,
+                Type::Top | Type::Any | Type::Unknown | Type::Error => target_ty,
+                _ => {
+                    self.error(
+                        format!("cannot index into {}", self.type_to_string(target_ty)),
+                        field,
+                    );
+                    ERROR_TYPE
+                }
+            };
+        }
+
+        if let AstNode::Variable = self.compiler.get_node(field) {
+            // TODO: typecheck dynamic field access such as `$rec.($name)`
+            self.typecheck_expr(field, STRING_TYPE);
+            return ANY_TYPE;
+        }
+
+        let target_ty = if let Type::List(elem_ty) = self.types[target_ty.0] {
+            elem_ty
+        } else {
+            target_ty
+        };
+
+        if let Type::OneOf(id) = self.types[target_ty.0] {
+            if self.oneof_types[id.0].contains(&NOTHING_TYPE) {
+                self.error(
+                    format!(
+                        "cannot access field `{}` of {}, which may be null",
+                        String::from_utf8_lossy(self.compiler.get_span_contents(field)),
+                        self.type_to_string(target_ty)
+                    ),
+                    field,
+                );
+                return ERROR_TYPE;
+            }
+        }
+
+        if let Some(field_ty) = self.record_field_type(target_ty, field) {
+            if let Type::List(_) = self.type_of(target) {
+                self.push_type(Type::List(field_ty))
+            } else {
+                field_ty
+            }
+        } else if self.is_env_type(target_ty) {
+            // Environment variables are not known statically
+            ANY_TYPE
+        } else {
+            self.error(
+                format!(
+                    "field `{}` does not exist in {}",
+                    String::from_utf8_lossy(self.compiler.get_span_contents(field)),
+                    self.type_to_string(target_ty)
+                ),
+                field,
+            );
+            ERROR_TYPE
+        }
+    }
+
+    /// Typecheck a parameter, returning the type of the arguments it accepts
+    ///
+    /// Parameters without a type annotation accept `inferred`, or the type of their default value
+    /// if nothing was inferred. The variable of a rest parameter holds a list of its arguments.
+    fn typecheck_param(&mut self, node_id: NodeId, inferred: TypeId) -> TypeId {
+        let AstNode::Param {
+            name,
+            ty,
+            default,
+            rest,
+        } = self.compiler.ast_nodes[node_id.0]
+        else {
+            panic!("internal error: param is not a param");
+        };
+
+        let mut ty_id = match ty {
+            Some(ty) => self.typecheck_type(ty),
+            None => inferred,
+        };
+
+        if let Some(default) = default {
+            let default_ty = self.typecheck_expr(default, ty_id);
+            if ty.is_none() && inferred == ANY_TYPE {
+                ty_id = default_ty;
+            }
+        }
+
+        let var_ty = if rest {
+            self.push_type(Type::List(ty_id))
+        } else {
+            ty_id
+        };
+        let var_id = self
+            .compiler
+            .var_resolution
+            .get(&name)
+            .expect("missing resolved variable");
+        self.variable_types[var_id.0] = var_ty;
+        self.set_node_type_id(node_id, ty_id);
+
+        ty_id
+    }
+
+    /// Infer the signature of a closure
+    ///
+    /// If a closure type is expected, unannotated parameters and `$in` get their types from it.
+    fn typecheck_closure(
+        &mut self,
+        params: Option<NodeId>,
+        block: NodeId,
+        expected: TypeId,
+    ) -> TypeId {
+        let expected_sig = match self.types[expected.0] {
+            Type::Closure(id) if expected != CLOSURE_TYPE => Some(self.closure_types[id.0].clone()),
+            _ => None,
+        };
+
+        let mut closure_ty = ClosureType {
+            params: vec![],
+            required: 0,
+            rest: None,
+            in_type: expected_sig.as_ref().map_or(ANY_TYPE, |sig| sig.in_type),
+            out_type: TOP_TYPE,
+        };
+
+        if let Some(params) = params {
+            for param in self.compiler.get_params(params).nodes.clone() {
+                let AstNode::Param { default, rest, .. } = self.compiler.ast_nodes[param.0] else {
+                    panic!("internal error: param is not a param");
+                };
+
+                let i = closure_ty.params.len();
+                let inferred = expected_sig
+                    .as_ref()
+                    .and_then(|sig| {
+                        if rest {
+                            sig.rest
+                        } else {
+                            sig.params.get(i).copied().or(sig.rest)
+                        }
+                    })
+                    .unwrap_or(ANY_TYPE);
+                let ty = self.typecheck_param(param, inferred);
+
+                if rest {
+                    closure_ty.rest = Some(ty);
+                } else {
+                    if default.is_none() {
+                        closure_ty.required = i + 1;
+                    }
+                    closure_ty.params.push(ty);
+                }
+            }
+            self.set_node_type_id(params, FORBIDDEN_TYPE);
+        }
+
+        self.input_types.push(closure_ty.in_type);
+        // The output type is checked against the expected one as part of the whole signature
+        closure_ty.out_type = self.typecheck_block(block, TOP_TYPE);
+        self.input_types.pop();
+
+        self.push_closure_type(closure_ty)
+    }
+
+    fn push_closure_type(&mut self, closure_ty: ClosureType) -> TypeId {
+        self.closure_types.push(closure_ty);
+        self.push_type(Type::Closure(ClosureTypeId(self.closure_types.len() - 1)))
+    }
+
+    /// Typecheck the row condition of `where` against the elements of the pipeline input
+    ///
+    /// Within the condition, both `$it` and `$in` refer to the current row.
+    fn typecheck_where(&mut self, closure: NodeId) -> TypeId {
+        let input_ty = self.input_types.last().copied().unwrap_or(ANY_TYPE);
+        let row_ty = match self.list_elem_type(input_ty) {
+            Some(row_ty) => row_ty,
+            None => {
+                self.error(
+                    format!(
+                        "where expects a list as input, got {}",
+                        self.type_to_string(input_ty)
+                    ),
+                    closure,
+                );
+                ANY_TYPE
             }
-            _ => panic!("internal error: unsupported node passed as binary op: {op:?}"),
+        };
+
+        let AstNode::Closure { block, .. } = self.compiler.get_node(closure) else {
+            panic!("internal error: row condition is not a closure");
+        };
+
+        self.row_types.push(row_ty);
+        self.input_types.push(row_ty);
+        self.typecheck_block(*block, BOOL_TYPE);
+        self.input_types.pop();
+        self.row_types.pop();
+
+        let closure_ty = self.push_closure_type(ClosureType {
+            params: vec![],
+            required: 0,
+            rest: None,
+            in_type: row_ty,
+            out_type: BOOL_TYPE,
+        });
+        self.set_node_type_id(closure, closure_ty);
+
+        // filtering keeps the type of the input
+        if self.is_subtype(input_ty, LIST_ANY_TYPE) {
+            input_ty
+        } else {
+            LIST_ANY_TYPE
+        }
+    }
+
+    /// Check whether the type is the type of `$env` or a union containing it
+    fn is_env_type(&self, ty: TypeId) -> bool {
+        match self.types[ty.0] {
+            Type::Record(RecordTypeId(0)) => true,
+            Type::OneOf(id) => self.oneof_types[id.0]
+                .iter()
+                .any(|member| self.is_env_type(*member)),
+            _ => false,
         }
     }
 
@@ -803,7 +1797,16 @@ impl<'a> Typechecker<'a> {
             .unwrap_or_default();
 
         self.typecheck_node(params);
+
+        let in_type = if in_out_types.is_empty() {
+            ANY_TYPE
+        } else {
+            let in_types = in_out_types.iter().map(|ty| ty.in_type).collect();
+            self.create_oneof(in_types)
+        };
+        self.input_types.push(in_type);
         self.typecheck_node(block);
+        self.input_types.pop();
         self.set_node_type_id(node_id, NONE_TYPE);
 
         // set input/output types for the command
@@ -824,6 +1827,88 @@ impl<'a> Typechecker<'a> {
         }
     }
 
+    /// Typecheck the bounds of the type parameters of a generic definition
+    fn typecheck_type_params(&mut self, type_params: Option<NodeId>) {
+        let Some(type_params) = type_params else {
+            return;
+        };
+
+        for type_param in self.compiler.get_params(type_params).nodes.clone() {
+            if let AstNode::TypeParam {
+                bound: Some(bound), ..
+            } = *self.compiler.get_node(type_param)
+            {
+                let bound_ty = self.typecheck_type(bound);
+                let type_decl_id = self.type_param_decl(type_param);
+                self.type_param_bounds.insert(type_decl_id, bound_ty);
+            }
+            // Type parameters are not supposed to be evaluated
+            self.set_node_type_id(type_param, FORBIDDEN_TYPE);
+        }
+        self.set_node_type_id(type_params, FORBIDDEN_TYPE);
+    }
+
+    fn type_param_decl(&self, type_param: NodeId) -> TypeDeclId {
+        let AstNode::TypeParam { name, .. } = self.compiler.get_node(type_param) else {
+            panic!("internal error: type parameter is not a type parameter");
+        };
+        self.compiler.type_resolution[name]
+    }
+
+    /// Typecheck the arguments of the attributes of a `Def` or `Extern` node
+    fn typecheck_attributes(&mut self, decl_node_id: NodeId) {
+        for attribute_id in self.compiler.get_attributes(decl_node_id).to_vec() {
+            let AstNode::Attribute { name, args } = *self.compiler.get_node(attribute_id) else {
+                panic!("internal error: expected attribute");
+            };
+            let args = self.compiler.lists[args.0].items.clone();
+
+            // expected argument types, the last one repeating if the count is unbounded
+            let (arg_types, count_ok, message): (&[TypeId], _, _) =
+                match self.compiler.get_span_contents(name) {
+                    b"example" => (
+                        &[STRING_TYPE, CLOSURE_TYPE],
+                        args.len() == 2,
+                        "`@example` expects a description and a code block",
+                    ),
+                    b"deprecated" => (
+                        &[STRING_TYPE],
+                        args.len() <= 1,
+                        "`@deprecated` expects at most one deprecation notice",
+                    ),
+                    b"search-terms" => (
+                        &[STRING_TYPE],
+                        !args.is_empty(),
+                        "`@search-terms` expects at least one search term",
+                    ),
+                    // unknown attributes are reported by the resolver
+                    _ => continue,
+                };
+
+            if !count_ok {
+                self.error(message, attribute_id);
+            }
+
+            for (i, arg) in args.iter().enumerate() {
+                let expected = arg_types.get(i).or(arg_types.last()).copied();
+                let expected = expected.unwrap_or(TOP_TYPE);
+                if matches!(self.compiler.ast_nodes[arg.0], AstNode::Name) {
+                    self.set_node_type_id(*arg, STRING_TYPE);
+                    if !self.constrain_subtype(STRING_TYPE, expected) {
+                        self.error(
+                            format!("Expected {}, got string", self.type_to_string(expected)),
+                            *arg,
+                        );
+                    }
+                } else {
+                    self.typecheck_expr(*arg, expected);
+                }
+            }
+
+            self.set_node_type_id(attribute_id, NONE_TYPE);
+        }
+    }
+
     fn typecheck_alias(&mut self, new_name: NodeId, old_name: NodeId, node_id: NodeId) {
         self.set_node_type_id(node_id, NONE_TYPE);
 
@@ -849,42 +1934,92 @@ impl<'a> Typechecker<'a> {
         if let Some(decl_id) = self.compiler.decl_resolution.get(&node_id) {
             let num_name_parts = self.compiler.decls[decl_id.0].name().split(' ').count();
             let decl_node_id = self.compiler.decl_nodes[decl_id.0];
-            let AstNode::Def {
-                type_params,
-                params,
-                ..
-            } = self.compiler.get_node(decl_node_id)
-            else {
-                panic!("Internal error: Expected def")
+            let (type_params, params) = match self.compiler.get_node(decl_node_id) {
+                AstNode::Def {
+                    type_params,
+                    params,
+                    ..
+                } => (type_params, params),
+                AstNode::Extern { params, .. } => (&None, params),
+                _ => panic!("Internal error: Expected def or extern"),
             };
             let params = self.compiler.get_params(*params);
 
-            let type_substs = if let Some(type_params) = type_params {
-                let mut type_substs = HashMap::new();
+            let mut type_substs = HashMap::new();
+            if let Some(type_params) = type_params {
                 for type_param in &self.compiler.get_params(*type_params).nodes {
-                    let type_decl_id = self.compiler.type_resolution[type_param];
+                    let type_decl_id = self.type_param_decl(*type_param);
                     let var = self.new_typevar(BOTTOM_TYPE, TOP_TYPE);
                     type_substs.insert(type_decl_id, var);
                 }
-                type_substs
-            } else {
-                HashMap::new()
-            };
+            }
+            // bounds may refer to other type parameters, so they're substituted once all
+            // variables exist
+            for (type_decl_id, var) in type_substs.clone() {
+                if let Some(bound) = self.type_param_bounds.get(&type_decl_id).copied() {
+                    self.type_vars[var.0].upper_bound = self.subst(bound, &type_substs);
+                }
+            }
+
+            let mut positional = vec![];
+            let mut required = 0;
+            let mut rest = None;
+            for param in &params.nodes {
+                let AstNode::Param {
+                    default,
+                    rest: is_rest,
+                    ..
+                } = self.compiler.get_node(*param)
+                else {
+                    panic!("internal error: param is not a param");
+                };
+                if *is_rest {
+                    rest = Some(*param);
+                } else {
+                    if default.is_none() {
+                        required = positional.len() + 1;
+                    }
+                    positional.push(*param);
+                }
+            }
 
             let num_args = parts.len() - num_name_parts;
-            if params.nodes.len() != num_args {
+            if rest.is_none() && required == positional.len() {
+                if num_args != positional.len() {
+                    self.error(
+                        format!(
+                            "Expected {} argument(s), got {}",
+                            positional.len(),
+                            num_args
+                        ),
+                        node_id,
+                    );
+                }
+            } else if num_args < required {
+                self.error(
+                    format!("Expected at least {required} argument(s), got {num_args}"),
+                    node_id,
+                );
+            } else if rest.is_none() && num_args > positional.len() {
                 self.error(
                     format!(
-                        "Expected {} argument(s), got {}",
-                        params.nodes.len(),
+                        "Expected at most {} argument(s), got {}",
+                        positional.len(),
                         num_args
                     ),
                     node_id,
                 );
             }
-            for (param, arg) in params.nodes.iter().zip(&parts[num_name_parts..]) {
-                let expected = self.type_id_of(*param);
-                let expected = self.subst(expected, &type_substs);
+
+            for (i, arg) in parts[num_name_parts..].iter().enumerate() {
+                // Arguments without a parameter are typechecked too
+                let expected = match positional.get(i).or(rest.as_ref()) {
+                    Some(param) => {
+                        let expected = self.type_id_of(*param);
+                        self.subst(expected, &type_substs)
+                    }
+                    None => TOP_TYPE,
+                };
                 if matches!(self.compiler.ast_nodes[arg.0], AstNode::Name) {
                     self.set_node_type_id(*arg, STRING_TYPE);
                     if !self.constrain_subtype(STRING_TYPE, expected) {
@@ -897,16 +2032,6 @@ impl<'a> Typechecker<'a> {
                     self.typecheck_expr(*arg, expected);
                 }
             }
-            if num_args > params.nodes.len() {
-                // Typecheck extra arguments too
-                for arg in &parts[num_name_parts + params.nodes.len()..] {
-                    if matches!(self.compiler.ast_nodes[arg.0], AstNode::Name) {
-                        self.set_node_type_id(*arg, STRING_TYPE);
-                    } else {
-                        self.typecheck_expr(*arg, TOP_TYPE);
-                    }
-                }
-            }
 
             // TODO base this on pipeline input type
             let out_types = self.decl_types[decl_id.0]
@@ -961,34 +2086,22 @@ impl<'a> Typechecker<'a> {
                 name,
                 args,
                 optional,
-            } => self.typecheck_type_ref(name, args, optional),
+            } => {
+                let ty_id = self.typecheck_type_ref(name, args);
+                self.optional_type(ty_id, optional)
+            }
             AstNode::RecordType {
-                fields,
-                optional: _, // TODO handle optional record types
+                fields, optional, ..
             } => {
-                let field_nodes = self.compiler.get_params(fields);
-                let mut fields = field_nodes
-                    .nodes
-                    .iter()
-                    .map(|field| {
-                        let AstNode::Param { name, ty } = self.compiler.get_node(*field) else {
-                            panic!("internal error: record field isn't Param");
-                        };
-                        let ty_id = match ty {
-                            Some(ty) => {
-                                self.typecheck_type(*ty);
-                                self.type_id_of(*ty)
-                            }
-                            None => ANY_TYPE,
-                        };
-                        (*name, ty_id)
-                    })
-                    .collect::<Vec<_>>();
-                // Store fields sorted by name
-                fields.sort_by_cached_key(|(name, _)| self.compiler.get_span_contents(*name));
-
-                self.record_types.push(fields);
-                self.push_type(Type::Record(RecordTypeId(self.record_types.len() - 1)))
+                let record_ty = self.typecheck_record_type(fields);
+                self.optional_type(record_ty, optional)
+            }
+            AstNode::TableType {
+                fields, optional, ..
+            } => {
+                let record_ty = self.typecheck_record_type(fields);
+                let table_ty = self.push_type(Type::List(record_ty));
+                self.optional_type(table_ty, optional)
             }
             _ => {
                 self.error(
@@ -1005,18 +2118,49 @@ impl<'a> Typechecker<'a> {
         ty_id
     }
 
-    fn typecheck_type_ref(
-        &mut self,
-        name_id: NodeId,
-        args_id: Option<NodeId>,
-        _optional: bool,
-    ) -> TypeId {
+    /// Typecheck the fields of a `record<...>` or `table<...>` type
+    fn typecheck_record_type(&mut self, fields: NodeId) -> TypeId {
+        let field_nodes = self.compiler.get_params(fields);
+        let mut fields = field_nodes
+            .nodes
+            .iter()
+            .map(|field| {
+                let AstNode::Param { name, ty, .. } = self.compiler.get_node(*field) else {
+                    panic!("internal error: record field isn't Param");
+                };
+                let ty_id = match ty {
+                    Some(ty) => {
+                        self.typecheck_type(*ty);
+                        self.type_id_of(*ty)
+                    }
+                    None => ANY_TYPE,
+                };
+                (*name, ty_id)
+            })
+            .collect::<Vec<_>>();
+        // Store fields sorted by name
+        fields.sort_by_cached_key(|(name, _)| self.compiler.get_span_contents(*name));
+
+        self.record_types.push(fields);
+        self.push_type(Type::Record(RecordTypeId(self.record_types.len() - 1)))
+    }
+
+    /// Make the type of an optional type annotation, such as `int?`, nullable
+    fn optional_type(&mut self, ty_id: TypeId, optional: bool) -> TypeId {
+        if optional {
+            self.create_oneof(HashSet::from([ty_id, NOTHING_TYPE]))
+        } else {
+            ty_id
+        }
+    }
+
+    fn typecheck_type_ref(&mut self, name_id: NodeId, args_id: Option<NodeId>) -> TypeId {
         let name = self.compiler.get_span_contents(name_id);
 
         // taken from parse_shape_name() in Nushell:
         match name {
             b"any" => ANY_TYPE,
-            // b"binary" => SyntaxShape::Binary,
+            b"binary" => BINARY_TYPE,
             // b"block" => // not possible to pass blocks
             b"list" => {
                 if let Some(args_id) = args_id {
@@ -1027,7 +2171,7 @@ impl<'a> Typechecker<'a> {
                         if args.len() > 1 {
                             let types =
                                 String::from_utf8_lossy(self.compiler.get_span_contents(args_id));
-                            self.error(format!("list must have only one type argument (to allow selection of types, use oneof{} -- WIP)", types), args_id);
+                            self.error(format!("list must have only one type argument (to allow selection of types, use oneof{})", types), args_id);
                             self.push_type(Type::List(UNKNOWN_TYPE))
                         } else if args.is_empty() {
                             self.error("list must have one type argument", args_id);
@@ -1044,34 +2188,66 @@ impl<'a> Typechecker<'a> {
                 }
             }
             b"bool" => BOOL_TYPE,
-            // b"cell-path" => SyntaxShape::CellPath,
-            b"closure" => CLOSURE_TYPE, //FIXME: Closures should have known output types
-            // b"datetime" => SyntaxShape::DateTime,
-            // b"directory" => SyntaxShape::Directory,
-            // b"duration" => SyntaxShape::Duration,
-            // b"error" => SyntaxShape::Error,
+            b"cell-path" => CELL_PATH_TYPE,
+            b"closure" => match args_id {
+                Some(args_id) => self.typecheck_closure_type(args_id),
+                None => CLOSURE_TYPE,
+            },
+            b"datetime" => DATE_TYPE,
+            // paths are plain strings, as in Nushell
+            b"directory" | b"path" => STRING_TYPE,
+            b"duration" => DURATION_TYPE,
+            b"error" => ERROR_VALUE_TYPE,
             b"float" => FLOAT_TYPE,
-            // b"filesize" => SyntaxShape::Filesize,
-            // b"glob" => SyntaxShape::GlobPattern,
+            b"filesize" => FILESIZE_TYPE,
+            b"glob" => GLOB_TYPE,
             b"int" => INT_TYPE,
-            // _ if bytes.starts_with(b"list") => parse_list_shape(working_set, bytes, span, use_loc),
             b"nothing" => NOTHING_TYPE,
             b"number" => NUMBER_TYPE,
-            // b"path" => SyntaxShape::Filepath,
-            // b"range" => SyntaxShape::Range,
-            // _ if bytes.starts_with(b"record") => {
-            //     parse_collection_shape(working_set, bytes, span, use_loc)
-            // }
+            b"oneof" => match args_id {
+                Some(args_id) => {
+                    self.typecheck_node(args_id);
+                    let args = &self.compiler.get_type_args(args_id).args;
+                    if args.is_empty() {
+                        self.error("oneof must have at least one type argument", args_id);
+                        UNKNOWN_TYPE
+                    } else {
+                        let types = args.iter().map(|arg| self.type_id_of(*arg)).collect();
+                        self.create_oneof(types)
+                    }
+                }
+                None => {
+                    self.error("oneof must have at least one type argument", name_id);
+                    UNKNOWN_TYPE
+                }
+            },
+            b"range" => RANGE_TYPE,
+            // record and table without fields; those with fields are `RecordType`/`TableType`
+            b"record" => {
+                self.record_types.push(Vec::new());
+                self.push_type(Type::Record(RecordTypeId(self.record_types.len() - 1)))
+            }
             b"string" => STRING_TYPE,
-            // _ if bytes.starts_with(b"table") => {
-            //     parse_collection_shape(working_set, bytes, span, use_loc)
-            // }
+            b"table" => {
+                self.record_types.push(Vec::new());
+                let record_ty =
+                    self.push_type(Type::Record(RecordTypeId(self.record_types.len() - 1)));
+                self.push_type(Type::List(record_ty))
+            }
             _ => {
                 // if bytes.contains(&b'@') {
                 //     // type with completion
                 // } else {
-                if let Some(type_decl) = self.compiler.type_resolution.get(&name_id) {
-                    self.push_type(Type::Ref(*type_decl))
+                if let Some(type_decl_id) = self.compiler.type_resolution.get(&name_id) {
+                    match self.compiler.type_decls[type_decl_id.0] {
+                        TypeDecl::Param(_) => match self.alias_args.get(type_decl_id) {
+                            Some(arg) => *arg,
+                            None => self.push_type(Type::Ref(*type_decl_id)),
+                        },
+                        TypeDecl::Alias(alias_id) => {
+                            self.expand_type_alias(*type_decl_id, alias_id, name_id, args_id)
+                        }
+                    }
                 } else {
                     UNKNOWN_TYPE
                 }
@@ -1080,6 +2256,137 @@ impl<'a> Typechecker<'a> {
         }
     }
 
+    /// Expand a reference to a type alias into the aliased type, with the alias's type parameters
+    /// bound to the given type arguments
+    fn expand_type_alias(
+        &mut self,
+        type_decl_id: TypeDeclId,
+        alias_id: NodeId,
+        name_id: NodeId,
+        args_id: Option<NodeId>,
+    ) -> TypeId {
+        let AstNode::TypeAlias {
+            name,
+            type_params,
+            ty,
+        } = *self.compiler.get_node(alias_id)
+        else {
+            panic!("internal error: type alias is not a type alias");
+        };
+
+        if self.expanding_aliases.contains(&type_decl_id) {
+            if self.recursive_aliases.insert(type_decl_id) {
+                self.error(
+                    format!(
+                        "type alias `{}` is recursive",
+                        String::from_utf8_lossy(self.compiler.get_span_contents(name))
+                    ),
+                    name,
+                );
+            }
+            return ERROR_TYPE;
+        }
+
+        let params = type_params
+            .map(|type_params| self.compiler.get_params(type_params).nodes.clone())
+            .unwrap_or_default();
+        let args = match args_id {
+            Some(args_id) => {
+                self.typecheck_node(args_id);
+                self.compiler.get_type_args(args_id).args.clone()
+            }
+            None => vec![],
+        };
+
+        if args.len() != params.len() {
+            self.error(
+                format!(
+                    "type `{}` expects {} type argument(s), got {}",
+                    String::from_utf8_lossy(self.compiler.get_span_contents(name)),
+                    params.len(),
+                    args.len()
+                ),
+                args_id.unwrap_or(name_id),
+            );
+        }
+
+        let mut shadowed = vec![];
+        for (i, param) in params.iter().enumerate() {
+            let param_decl_id = self.type_param_decl(*param);
+            let arg_ty = args.get(i).map_or(ANY_TYPE, |arg| self.type_id_of(*arg));
+            if let (Some(arg), Some(bound)) = (
+                args.get(i),
+                self.type_param_bounds.get(&param_decl_id).copied(),
+            ) {
+                if !self.is_subtype(arg_ty, bound) {
+                    self.error(
+                        format!(
+                            "Expected {}, got {}",
+                            self.type_to_string(bound),
+                            self.type_to_string(arg_ty)
+                        ),
+                        *arg,
+                    );
+                }
+            }
+            shadowed.push((param_decl_id, self.alias_args.insert(param_decl_id, arg_ty)));
+        }
+
+        // keep the node types of the alias body as typechecked at its declaration
+        let body_nodes = name.0 + 1..alias_id.0;
+        let body_types = self.node_types[body_nodes.clone()].to_vec();
+
+        self.expanding_aliases.push(type_decl_id);
+        let ty_id = self.typecheck_type(ty);
+        self.expanding_aliases.pop();
+
+        self.node_types.splice(body_nodes, body_types);
+
+        for (param_decl_id, prev) in shadowed {
+            match prev {
+                Some(prev) => self.alias_args.insert(param_decl_id, prev),
+                None => self.alias_args.remove(&param_decl_id),
+            };
+        }
+
+        ty_id
+    }
+
+    /// Typecheck the type arguments of `closure<P1, P2, ..., In -> Out>`, listing the parameter
+    /// types and, optionally, the input and output type last
+    fn typecheck_closure_type(&mut self, args_id: NodeId) -> TypeId {
+        let args = self.compiler.get_type_args(args_id).args.clone();
+        let mut closure_ty = ClosureType {
+            params: vec![],
+            required: 0,
+            rest: None,
+            in_type: ANY_TYPE,
+            out_type: ANY_TYPE,
+        };
+
+        for (i, arg) in args.iter().enumerate() {
+            if let AstNode::InOutType(in_ty, out_ty) = self.compiler.get_node(*arg) {
+                if i != args.len() - 1 {
+                    self.error(
+                        "input and output types must come last in closure type",
+                        *arg,
+                    );
+                }
+                closure_ty.in_type = self.typecheck_type(*in_ty);
+                closure_ty.out_type = self.typecheck_type(*out_ty);
+                self.set_node_type_id(*arg, FORBIDDEN_TYPE);
+            } else {
+                let param_ty = self.typecheck_type(*arg);
+                closure_ty.params.push(param_ty);
+            }
+        }
+        closure_ty.required = closure_ty.params.len();
+
+        // Type argument lists are not supposed to be evaluated
+        self.set_node_type_id(args_id, FORBIDDEN_TYPE);
+        self.push_closure_type(closure_ty)
+    }
+
     /// Add a new type and return its ID. To save space, common types are not pushed and their ID is
     /// returned directly.
     fn push_type(&mut self, ty: Type) -> TypeId {
@@ -1097,7 +2404,15 @@ impl<'a> Typechecker<'a> {
             Type::Float => FLOAT_TYPE,
             Type::Bool => BOOL_TYPE,
             Type::String => STRING_TYPE,
-            Type::Closure => CLOSURE_TYPE,
+            Type::Binary => BINARY_TYPE,
+            Type::CellPath => CELL_PATH_TYPE,
+            Type::Date => DATE_TYPE,
+            Type::Duration => DURATION_TYPE,
+            Type::Filesize => FILESIZE_TYPE,
+            Type::Glob => GLOB_TYPE,
+            Type::Range => RANGE_TYPE,
+            Type::ErrorValue => ERROR_VALUE_TYPE,
+            Type::Closure(ClosureTypeId(0)) => CLOSURE_TYPE,
             Type::List(ANY_TYPE) => LIST_ANY_TYPE,
             _ => {
                 self.types.push(ty);
@@ -1126,8 +2441,33 @@ impl<'a> Typechecker<'a> {
             | Type::Bool
             | Type::String
             | Type::Binary
+            | Type::CellPath
+            | Type::Date
+            | Type::Duration
+            | Type::Filesize
+            | Type::Glob
+            | Type::Range
+            | Type::ErrorValue
             | Type::Var(_) => ty_id,
-            Type::Closure => todo!(),
+            Type::Closure(id) => {
+                let orig = self.closure_types[id.0].clone();
+                let closure_ty = ClosureType {
+                    params: orig
+                        .params
+                        .iter()
+                        .map(|param| self.subst(*param, substs))
+                        .collect(),
+                    required: orig.required,
+                    rest: orig.rest.map(|rest| self.subst(rest, substs)),
+                    in_type: self.subst(orig.in_type, substs),
+                    out_type: self.subst(orig.out_type, substs),
+                };
+                if closure_ty == orig {
+                    ty_id
+                } else {
+                    self.push_closure_type(closure_ty)
+                }
+            }
             Type::List(elem_ty) => {
                 let new_elem = self.subst(elem_ty, substs);
                 if elem_ty == new_elem {
@@ -1192,6 +2532,64 @@ impl<'a> Typechecker<'a> {
         }
     }
 
+    /// Like `extract_elem_type()`, but also extracts element types of lists within union types
+    fn list_elem_type(&mut self, ty: TypeId) -> Option<TypeId> {
+        if let Type::OneOf(id) = self.types[ty.0] {
+            let elem_types = self.oneof_types[id.0]
+                .clone()
+                .into_iter()
+                .filter_map(|member| self.extract_elem_type(member))
+                .collect::<HashSet<_>>();
+            if elem_types.is_empty() {
+                None
+            } else {
+                Some(self.create_oneof(elem_types))
+            }
+        } else {
+            self.extract_elem_type(ty)
+        }
+    }
+
+    /// Given the type of a record, get the type of its field `name`
+    ///
+    /// Returns `None` if none of the possible record types have such a field.
+    fn record_field_type(&mut self, record_ty: TypeId, name: NodeId) -> Option<TypeId> {
+        match self.types[record_ty.0] {
+            Type::Record(id) => {
+                let name_text = self.compiler.get_span_contents(name);
+                self.record_types[id.0]
+                    .iter()
+                    .find(|(field, _)| self.compiler.get_span_contents(*field) == name_text)
+                    .map(|(_, ty)| *ty)
+            }
+            Type::OneOf(id) => {
+                let field_types = self.oneof_types[id.0]
+                    .clone()
+                    .into_iter()
+                    .filter_map(|member| self.record_field_type(member, name))
+                    .collect::<HashSet<_>>();
+                if field_types.is_empty() {
+                    None
+                } else {
+                    Some(self.create_oneof(field_types))
+                }
+            }
+            Type::Top => Some(TOP_TYPE),
+            Type::Bottom => Some(BOTTOM_TYPE),
+            Type::Any => Some(ANY_TYPE),
+            Type::Unknown => Some(UNKNOWN_TYPE),
+            Type::Error => Some(ERROR_TYPE),
+            _ => None,
+        }
+    }
+
+    fn set_variable_type(&mut self, var_name: NodeId, type_id: TypeId) {
+        // Variables that could not be defined have been reported by the resolver
+        if let Some(var_id) = self.compiler.var_resolution.get(&var_name) {
+            self.variable_types[var_id.0] = type_id;
+        }
+    }
+
     fn set_node_type_id(&mut self, node_id: NodeId, type_id: TypeId) {
         self.node_types[node_id.0] = type_id;
     }
@@ -1214,7 +2612,8 @@ impl<'a> Typechecker<'a> {
         }
         match (self.types[sub_id.0], self.types[supe_id.0]) {
             (_, Type::Top | Type::Any | Type::Unknown) => true,
-            (Type::Bottom | Type::Any | Type::Unknown, _) => true,
+            // Errors have already been reported for values of the error type
+            (Type::Bottom | Type::Any | Type::Unknown | Type::Error, _) => true,
             (Type::Int | Type::Float | Type::Number, Type::Number) => true,
             (Type::List(inner_sub), Type::List(inner_supe)) => {
                 self.constrain_subtype(inner_sub, inner_supe)
@@ -1250,6 +2649,37 @@ impl<'a> Typechecker<'a> {
 
                 true
             }
+            (Type::Closure(sub_closure_id), Type::Closure(supe_closure_id)) => {
+                let sub = self.closure_types[sub_closure_id.0].clone();
+                let supe = self.closure_types[supe_closure_id.0].clone();
+
+                // Inputs and parameters are contravariant, the output is covariant
+                if !self.constrain_subtype(supe.in_type, sub.in_type)
+                    || !self.constrain_subtype(sub.out_type, supe.out_type)
+                    || (sub.required > supe.params.len() && supe.rest.is_none())
+                {
+                    return false;
+                }
+
+                for (i, supe_param) in supe.params.iter().enumerate() {
+                    // Arguments the closure has no parameters for are ignored
+                    if let Some(sub_param) = sub.params.get(i).copied().or(sub.rest) {
+                        if !self.constrain_subtype(*supe_param, sub_param) {
+                            return false;
+                        }
+                    }
+                }
+
+                if let Some(supe_rest) = supe.rest {
+                    for sub_param in sub.params.iter().skip(supe.params.len()).chain(&sub.rest) {
+                        if !self.constrain_subtype(supe_rest, *sub_param) {
+                            return false;
+                        }
+                    }
+                }
+
+                true
+            }
             (Type::Var(var_id), _) => {
                 let lb = self.type_vars[var_id.0].lower_bound;
                 let ub = self.type_vars[var_id.0].upper_bound;
@@ -1292,6 +2722,10 @@ impl<'a> Typechecker<'a> {
                     false
                 }
             }
+            (Type::Ref(decl_id), _) if self.type_param_bounds.contains_key(&decl_id) => {
+                // A bounded type parameter can be used wherever its bound can
+                self.constrain_subtype(self.type_param_bounds[&decl_id], supe_id)
+            }
             (Type::OneOf(id), _) => self.oneof_types[id.0]
                 .clone()
                 .iter()
@@ -1316,7 +2750,8 @@ impl<'a> Typechecker<'a> {
         }
         match (self.types[sub.0], self.types[supe.0]) {
             (_, Type::Top | Type::Any | Type::Unknown) => true,
-            (Type::Bottom | Type::Any | Type::Unknown, _) => true,
+            // Errors have already been reported for values of the error type
+            (Type::Bottom | Type::Any | Type::Unknown | Type::Error, _) => true,
             (Type::Int | Type::Float | Type::Number, Type::Number) => true,
             (Type::List(inner_sub), Type::List(inner_supe)) => {
                 self.is_subtype(inner_sub, inner_supe)
@@ -1352,6 +2787,28 @@ impl<'a> Typechecker<'a> {
 
                 true
             }
+            (Type::Closure(sub_closure_id), Type::Closure(supe_closure_id)) => {
+                let sub = &self.closure_types[sub_closure_id.0];
+                let supe = &self.closure_types[supe_closure_id.0];
+
+                self.is_subtype(supe.in_type, sub.in_type)
+                    && self.is_subtype(sub.out_type, supe.out_type)
+                    && (sub.required <= supe.params.len() || supe.rest.is_some())
+                    && supe.params.iter().enumerate().all(|(i, supe_param)| {
+                        sub.params
+                            .get(i)
+                            .copied()
+                            .or(sub.rest)
+                            .is_none_or(|sub_param| self.is_subtype(*supe_param, sub_param))
+                    })
+                    && supe.rest.is_none_or(|supe_rest| {
+                        sub.params
+                            .iter()
+                            .skip(supe.params.len())
+                            .chain(&sub.rest)
+                            .all(|sub_param| self.is_subtype(supe_rest, *sub_param))
+                    })
+            }
             (Type::Var(var_id), _) => {
                 let var = &self.type_vars[var_id.0];
                 self.is_subtype(var.upper_bound, supe)
@@ -1360,6 +2817,9 @@ impl<'a> Typechecker<'a> {
                 let var = &self.type_vars[var_id.0];
                 self.is_subtype(sub, var.lower_bound)
             }
+            (Type::Ref(decl_id), _) if self.type_param_bounds.contains_key(&decl_id) => {
+                self.is_subtype(self.type_param_bounds[&decl_id], supe)
+            }
             (Type::OneOf(id), _) => self.oneof_types[id.0]
                 .clone()
                 .iter()
@@ -1395,8 +2855,40 @@ impl<'a> Typechecker<'a> {
             | Type::Bool
             | Type::String
             | Type::Binary
+            | Type::CellPath
+            | Type::Date
+            | Type::Duration
+            | Type::Filesize
+            | Type::Glob
+            | Type::Range
+            | Type::ErrorValue
             | Type::Ref(_) => ty_id,
-            Type::Closure => ty_id,
+            Type::Closure(id) => {
+                let orig = self.closure_types[id.0].clone();
+                let closure_ty = ClosureType {
+                    params: orig
+                        .params
+                        .iter()
+                        .map(|param| self.eliminate_type_vars(*param, max_var, !use_lower))
+                        .collect(),
+                    required: orig.required,
+                    rest: orig
+                        .rest
+                        .map(|rest| self.eliminate_type_vars(rest, max_var, !use_lower)),
+                    in_type: self.eliminate_type_vars(orig.in_type, max_var, !use_lower),
+                    out_type: self.eliminate_type_vars(orig.out_type, max_var, use_lower),
+                };
+
+                if closure_ty.params == orig.params
+                    && closure_ty.rest == orig.rest
+                    && closure_ty.in_type == orig.in_type
+                    && closure_ty.out_type == orig.out_type
+                {
+                    ty_id
+                } else {
+                    self.push_closure_type(closure_ty)
+                }
+            }
             Type::List(inner_ty) => {
                 let new_inner = self.eliminate_type_vars(inner_ty, max_var, use_lower);
                 if inner_ty == new_inner {
@@ -1481,8 +2973,36 @@ impl<'a> Typechecker<'a> {
             Type::Float => "float".to_string(),
             Type::Bool => "bool".to_string(),
             Type::Binary => "binary".to_string(),
+            Type::CellPath => "cell-path".to_string(),
+            Type::Date => "datetime".to_string(),
+            Type::Duration => "duration".to_string(),
+            Type::Filesize => "filesize".to_string(),
+            Type::Glob => "glob".to_string(),
+            Type::Range => "range".to_string(),
+            Type::ErrorValue => "error".to_string(),
             Type::String => "string".to_string(),
-            Type::Closure => "closure".to_string(),
+            Type::Closure(ClosureTypeId(0)) => "closure".to_string(),
+            Type::Closure(id) => {
+                let closure_ty = &self.closure_types[id.0];
+                let mut fmt = "closure<".to_string();
+                for (i, param) in closure_ty.params.iter().enumerate() {
+                    fmt += &self.type_to_string(*param);
+                    if i >= closure_ty.required {
+                        fmt.push('?');
+                    }
+                    fmt += ", ";
+                }
+                if let Some(rest) = closure_ty.rest {
+                    fmt += "...";
+                    fmt += &self.type_to_string(rest);
+                    fmt += ", ";
+                }
+                fmt += &self.type_to_string(closure_ty.in_type);
+                fmt += " -> ";
+                fmt += &self.type_to_string(closure_ty.out_type);
+                fmt.push('>');
+                fmt
+            }
             Type::List(subtype_id) => {
                 format!("list<{}>", self.type_to_string(*subtype_id))
             }
@@ -1543,6 +3063,12 @@ impl<'a> Typechecker<'a> {
                 TypeDecl::Param(name_node) => {
                     String::from_utf8_lossy(self.compiler.get_span_contents(name_node)).to_string()
                 }
+                TypeDecl::Alias(alias_id) => {
+                    let AstNode::TypeAlias { name, .. } = self.compiler.get_node(alias_id) else {
+                        panic!("internal error: type alias is not a type alias");
+                    };
+                    String::from_utf8_lossy(self.compiler.get_span_contents(*name)).to_string()
+                }
             },
             Type::Var(type_var_id) => {
                 let var = &self.type_vars[type_var_id.0];
@@ -1556,6 +3082,23 @@ impl<'a> Typechecker<'a> {
         }
     }
 
+    /// Check if there can be a value that has both types
+    fn types_overlap(&self, lhs: TypeId, rhs: TypeId) -> bool {
+        match (self.types[lhs.0], self.types[rhs.0]) {
+            (Type::OneOf(id), _) => self.oneof_types[id.0]
+                .iter()
+                .any(|ty| self.types_overlap(*ty, rhs)),
+            (_, Type::OneOf(id)) => self.oneof_types[id.0]
+                .iter()
+                .any(|ty| self.types_overlap(lhs, *ty)),
+            (lhs_ty, rhs_ty) => {
+                self.is_subtype(lhs, rhs)
+                    || self.is_subtype(rhs, lhs)
+                    || self.is_type_compatible(lhs_ty, rhs_ty)
+            }
+        }
+    }
+
     /// Check if one type can be cast to another type
     fn is_type_compatible(&self, lhs: Type, rhs: Type) -> bool {
         match (lhs, rhs) {
@@ -1607,6 +3150,14 @@ impl<'a> Typechecker<'a> {
         })
     }
 
+    fn warning(&mut self, msg: impl Into<String>, node_id: NodeId) {
+        self.errors.push(SourceError {
+            message: msg.into(),
+            node_id,
+            severity: Severity::Warning,
+        })
+    }
+
     fn binary_op_err(&mut self, op_msg: &str, lhs: NodeId, op: NodeId, rhs: NodeId) {
         self.error(
             format!(
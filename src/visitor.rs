@@ -0,0 +1,221 @@
+use crate::compiler::Compiler;
+use crate::parser::{AstNode, NodeId};
+
+/// Hooks called by [walk] on every node of a subtree
+pub trait Visitor {
+    /// Called before the children of the node are visited. Returning `false` skips the children
+    /// (and the `exit` call of the node).
+    fn enter(&mut self, _compiler: &Compiler, _node_id: NodeId) -> bool {
+        true
+    }
+
+    /// Called after the children of the node have been visited
+    fn exit(&mut self, _compiler: &Compiler, _node_id: NodeId) {}
+}
+
+/// Visit the subtree rooted at `node_id` depth-first, with children in source order
+pub fn walk(compiler: &Compiler, node_id: NodeId, visitor: &mut impl Visitor) {
+    if !visitor.enter(compiler, node_id) {
+        return;
+    }
+
+    for child in children(compiler, node_id) {
+        walk(compiler, child, visitor);
+    }
+
+    visitor.exit(compiler, node_id);
+}
+
+/// Get the direct children of a node in source order, including the nodes stored in side tables
+/// (e.g., the items of a list) and the attributes of a `def` or `extern`
+pub fn children(compiler: &Compiler, node_id: NodeId) -> Vec<NodeId> {
+    let mut children = vec![];
+
+    // No `_` arm on purpose: a new node kind must say what its children are
+    match *compiler.get_node(node_id) {
+        AstNode::Int
+        | AstNode::Float
+        | AstNode::String
+        | AstNode::Name
+        | AstNode::Variable
+        | AstNode::True
+        | AstNode::False
+        | AstNode::Null
+        | AstNode::Pow
+        | AstNode::Multiply
+        | AstNode::Divide
+        | AstNode::FloorDiv
+        | AstNode::Modulo
+        | AstNode::Plus
+        | AstNode::Minus
+        | AstNode::Equal
+        | AstNode::NotEqual
+        | AstNode::LessThan
+        | AstNode::GreaterThan
+        | AstNode::LessThanOrEqual
+        | AstNode::GreaterThanOrEqual
+        | AstNode::RegexMatch
+        | AstNode::NotRegexMatch
+        | AstNode::In
+        | AstNode::NotIn
+        | AstNode::Has
+        | AstNode::NotHas
+        | AstNode::StartsWith
+        | AstNode::EndsWith
+        | AstNode::Append
+        | AstNode::BitAnd
+        | AstNode::BitXor
+        | AstNode::BitOr
+        | AstNode::ShiftLeft
+        | AstNode::ShiftRight
+        | AstNode::And
+        | AstNode::Xor
+        | AstNode::Or
+        | AstNode::Assignment
+        | AstNode::AddAssignment
+        | AstNode::SubtractAssignment
+        | AstNode::MultiplyAssignment
+        | AstNode::DivideAssignment
+        | AstNode::AppendAssignment
+        | AstNode::Break
+        | AstNode::Continue
+        | AstNode::FlagLong
+        | AstNode::FlagShort
+        | AstNode::FlagShortGroup
+        | AstNode::ImplicitIt
+        | AstNode::PatternWildcard
+        | AstNode::PatternBinding
+        | AstNode::Garbage => {}
+        AstNode::Type { name, args, .. } => {
+            children.push(name);
+            children.extend(args);
+        }
+        AstNode::TypeArgs(_) => children.extend(&compiler.get_type_args(node_id).args),
+        AstNode::TypeParam { name, bound } => {
+            children.push(name);
+            children.extend(bound);
+        }
+        AstNode::RecordType { name, fields, .. } | AstNode::TableType { name, fields, .. } => {
+            children.extend([name, fields])
+        }
+        AstNode::Let {
+            variable_name,
+            ty,
+            initializer,
+            ..
+        } => {
+            children.push(variable_name);
+            children.extend(ty);
+            children.push(initializer);
+        }
+        AstNode::While { condition, block } => children.extend([condition, block]),
+        AstNode::For {
+            variable,
+            range,
+            block,
+        } => children.extend([variable, range, block]),
+        AstNode::Loop { block } => children.push(block),
+        AstNode::Return(value) => children.extend(value),
+        AstNode::Def {
+            name,
+            type_params,
+            params,
+            in_out_types,
+            block,
+            ..
+        } => {
+            children.extend(compiler.get_attributes(node_id));
+            children.push(name);
+            children.extend(type_params);
+            children.push(params);
+            children.extend(in_out_types);
+            children.push(block);
+        }
+        AstNode::Extern { name, params } => {
+            children.extend(compiler.get_attributes(node_id));
+            children.extend([name, params]);
+        }
+        AstNode::Attribute { name, args } => {
+            children.push(name);
+            children.extend(&compiler.lists[args.0].items);
+        }
+        AstNode::Params(_) => children.extend(&compiler.get_params(node_id).nodes),
+        AstNode::Param {
+            name, ty, default, ..
+        } => {
+            children.push(name);
+            children.extend(ty);
+            children.extend(default);
+        }
+        AstNode::InOutTypes(_) => children.extend(&compiler.get_in_out_types(node_id).nodes),
+        AstNode::InOutType(in_ty, out_ty) => children.extend([in_ty, out_ty]),
+        AstNode::Closure { params, block } => {
+            children.extend(params);
+            children.push(block);
+        }
+        AstNode::Alias { new_name, old_name } => children.extend([new_name, old_name]),
+        AstNode::TypeAlias {
+            name,
+            type_params,
+            ty,
+        } => {
+            children.push(name);
+            children.extend(type_params);
+            children.push(ty);
+        }
+        AstNode::Call(_) => children.extend(&compiler.get_call(node_id).parts),
+        AstNode::NamedValue { name, value } => children.extend([name, value]),
+        AstNode::BinaryOp { lhs, op, rhs } => children.extend([lhs, op, rhs]),
+        AstNode::Range { lhs, rhs } => children.extend([lhs, rhs]),
+        AstNode::PatternRange { lhs, rhs } => children.extend(lhs.into_iter().chain(rhs)),
+        AstNode::List(list_id) | AstNode::PatternList(list_id) | AstNode::PatternOr(list_id) => {
+            children.extend(&compiler.lists[list_id.0].items)
+        }
+        AstNode::Table(_) => {
+            let table = compiler.get_table(node_id);
+            children.push(table.header);
+            children.extend(&table.rows);
+        }
+        AstNode::Record(record_id) | AstNode::PatternRecord(record_id) => {
+            for (key, value) in &compiler.records[record_id.0].pairs {
+                children.extend([*key, *value]);
+            }
+        }
+        AstNode::MemberAccess { target, field } => children.extend([target, field]),
+        AstNode::Block(_) => children.extend(&compiler.get_block(node_id).nodes),
+        AstNode::Pipeline(pipeline_id) => {
+            children.extend(compiler.pipelines[pipeline_id.0].get_expressions())
+        }
+        AstNode::If {
+            condition,
+            then_block,
+            else_block,
+        } => {
+            children.extend([condition, then_block]);
+            children.extend(else_block);
+        }
+        AstNode::Try {
+            try_block,
+            catch_block,
+            finally_block,
+        } => {
+            children.push(try_block);
+            children.extend(catch_block);
+            children.extend(finally_block);
+        }
+        AstNode::Match(_) => {
+            let match_node = compiler.get_match(node_id);
+            children.push(match_node.target);
+            for (pattern, result) in &match_node.match_arms {
+                children.extend([*pattern, *result]);
+            }
+        }
+        AstNode::Where(closure) => children.push(closure),
+        AstNode::EnvShorthand { name, value, expr } => children.extend([name, value, expr]),
+        AstNode::Statement(node) | AstNode::PatternValue(node) => children.push(node),
+        AstNode::PatternRest(binding) => children.extend(binding),
+        AstNode::PatternGuard { pattern, guard } => children.extend([pattern, guard]),
+    }
+
+    children
+}